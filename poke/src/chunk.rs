@@ -1,9 +1,9 @@
 // LINK - https://craftinginterpreters.com/chunks-of-bytecode.html
 
 use macros::AllVariants;
-use std::{arch::x86_64::_SIDD_LEAST_SIGNIFICANT, collections::HashMap, fmt};
+use std::fmt;
 
-use crate::value::ValueType;
+use crate::{parser::tokens::Span, value::ValueType};
 
 // REVIEW - Consider using variant parameters
 #[repr(u8)]
@@ -12,6 +12,7 @@ pub enum ByteCode {
     /// Single byte instruction.
     ///
     /// Represents the `OP_RETURN` instruction, which indicates the end of a function or method.
+    /// Functions return the value on top of the stack, while the script just ends.
     Return,
 
     /// 2 byte instruction.
@@ -30,15 +31,272 @@ pub enum ByteCode {
     /// - 4: Highest byte of the index
     ConstantLong,
 
+    /// Single byte instruction.
+    ///
+    /// Pushes `nil`.
+    Nil,
+
+    /// Single byte instruction.
+    ///
+    /// Pushes `true`.
+    True,
+
+    /// Single byte instruction.
+    ///
+    /// Pushes `false`.
+    False,
+
     Add,
     Subtract,
     Multiply,
     Divide,
+    Modulo,
+    Power,
+    IntDivide,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+
+    /// Single byte instruction.
+    ///
+    /// Pops two strings or numbers, pushing the string joining their text.
+    Concat,
+
+    /// Single byte instruction.
+    ///
+    /// Replaces the value on top of the stack by a string of its text, as
+    /// printed by `print`.
+    ToString,
+
+    /// Single byte instruction.
+    ///
+    /// Pops two values, pushing whether they are equal. Numbers of different
+    /// types are equal when they have the same value.
+    Equal,
+
+    /// Single byte instruction.
+    ///
+    /// Pops two numbers, pushing whether the first is less than the second.
+    Less,
+
+    /// Single byte instruction.
+    ///
+    /// Pops two numbers, pushing whether the first is greater than the second.
+    Greater,
 
     /// Single byte instruction.
     ///
     /// Represents the `OP_NEGATE` instruction, which negates a given `Value`.
     Negate,
+
+    /// Single byte instruction.
+    ///
+    /// Replaces the value on top of the stack by whether it's falsey.
+    Not,
+
+    /// Single byte instruction.
+    ///
+    /// Flips the bits of the int or byte on top of the stack.
+    BitNot,
+
+    /// Single byte instruction.
+    ///
+    /// Replaces the string on top of the stack by its length in bytes, or the
+    /// table by its border.
+    Len,
+
+    /// Single byte instruction.
+    ///
+    /// Pops and prints the value on top of the stack.
+    Print,
+
+    /// Single byte instruction.
+    ///
+    /// Discards the value on top of the stack.
+    Pop,
+
+    /// 2 bytes instruction.
+    ///
+    /// Discards many values from the top of the stack, like the locals of a scope.
+    /// - 1: Opcode (`OP_POPN`)
+    /// - 2: Amount of values discarded
+    PopN,
+
+    /// 3 bytes instruction.
+    ///
+    /// Pops the value on top of the stack into a new global variable.
+    /// - 1: Opcode (`OP_DEFINE_GLOBAL`)
+    /// - 2: Lowest byte of the variable's name index in the identifiers table
+    /// - 3: Highest byte of the name index
+    DefineGlobal,
+
+    /// 3 bytes instruction.
+    ///
+    /// Pushes the value of a global variable. Operands are the same as `DefineGlobal`.
+    GetGlobal,
+
+    /// 3 bytes instruction.
+    ///
+    /// Pops the value on top of the stack into an existing global variable.
+    /// Operands are the same as `DefineGlobal`.
+    SetGlobal,
+
+    /// 2 bytes instruction.
+    ///
+    /// Pushes the value of a local variable.
+    /// - 1: Opcode (`OP_GET_LOCAL`)
+    /// - 2: Stack slot of the variable
+    GetLocal,
+
+    /// 2 bytes instruction.
+    ///
+    /// Pops the value on top of the stack into a local variable.
+    /// - 1: Opcode (`OP_SET_LOCAL`)
+    /// - 2: Stack slot of the variable
+    SetLocal,
+
+    /// 2 bytes instruction.
+    ///
+    /// Checks that the value on top of the stack can be stored in a variable
+    /// of the given type, converting it if needed.
+    /// - 1: Opcode (`OP_CHECK_TYPE`)
+    /// - 2: The `DataType` discriminant, with `DataType::NULLABLE` set when
+    ///   `nil` is also accepted
+    CheckType,
+
+    /// Single byte instruction.
+    ///
+    /// Pushes a new empty table.
+    NewTable,
+
+    /// Single byte instruction.
+    ///
+    /// Pops a value and its key, adding them to the table below them, which
+    /// stays in the stack for the next field of its constructor.
+    InitField,
+
+    /// Single byte instruction.
+    ///
+    /// Pops a key and the table below it, pushing the value of the key.
+    GetIndex,
+
+    /// Single byte instruction.
+    ///
+    /// Pops a value, a key and the table below them, assigning the value to
+    /// the key. The value is pushed back, like the result of an expression.
+    SetIndex,
+
+    /// 3 bytes instruction.
+    ///
+    /// Moves the instruction pointer forward.
+    /// - 1: Opcode (`OP_JUMP`)
+    /// - 2: Lowest byte of the offset, counted from the next instruction
+    /// - 3: Highest byte of the offset
+    Jump,
+
+    /// 3 bytes instruction.
+    ///
+    /// Moves the instruction pointer forward if the value on top of the stack
+    /// is falsey, without popping it. Operands are the same as `Jump`.
+    JumpIfFalse,
+
+    /// 3 bytes instruction.
+    ///
+    /// Moves the instruction pointer backwards, to the start of a loop.
+    /// Operands are the same as `Jump`.
+    Loop,
+
+    /// 3 bytes instruction.
+    ///
+    /// Prepares a numeric `for` loop from the start, limit and step on top of
+    /// the stack, pushing the loop variable. Jumps forward, past the loop,
+    /// if it doesn't run at all. Operands are the same as `Jump`.
+    ForPrep,
+
+    /// 3 bytes instruction.
+    ///
+    /// Steps the counter of a numeric `for` loop, jumping back to the start
+    /// of its body with the new loop variable while it's within the limit.
+    /// Operands are the same as `Loop`.
+    ForLoop,
+
+    /// Single byte instruction.
+    ///
+    /// Calls the iterator of a generic `for` loop with its state and control
+    /// value, found on top of the stack. Iterating over a table pushes its key
+    /// after the control value instead.
+    ForCall,
+
+    /// 4 bytes instruction.
+    ///
+    /// Jumps forward, past the generic `for` loop, if the iterator returned
+    /// `nil`, otherwise keeps it as the control value and pushes the loop
+    /// variables. Over a table, the second variable is the value of the key.
+    /// - 1: Opcode (`OP_FOR_CALL_LOOP`)
+    /// - 2: Amount of loop variables
+    /// - 3: Lowest byte of the offset, counted from the next instruction
+    /// - 4: Highest byte of the offset
+    ForCallLoop,
+
+    /// 2 bytes instruction.
+    ///
+    /// Calls the value below the arguments on top of the stack.
+    /// - 1: Opcode (`OP_CALL`)
+    /// - 2: Amount of arguments
+    Call,
+
+    /// 2 bytes instruction.
+    ///
+    /// Pushes a reference to a local variable, the argument of a `mut` parameter.
+    /// A local already holding a reference pushes that same reference.
+    /// - 1: Opcode (`OP_REF_LOCAL`)
+    /// - 2: Stack slot of the variable
+    RefLocal,
+
+    /// 3 bytes instruction.
+    ///
+    /// Pushes a reference to a global variable, the argument of a `mut` parameter.
+    /// Operands are the same as `DefineGlobal`.
+    RefGlobal,
+
+    /// 2 bytes instruction.
+    ///
+    /// Pushes a reference to a variable captured by the current closure, the
+    /// argument of a `mut` parameter.
+    /// - 1: Opcode (`OP_REF_UPVALUE`)
+    /// - 2: Index of the upvalue
+    RefUpvalue,
+
+    /// Variable length instruction.
+    ///
+    /// Wraps the function on top of the stack into a closure, capturing the
+    /// given variables.
+    /// - 1: Opcode (`OP_CLOSURE`)
+    /// - 2: Amount of upvalues
+    /// - Then for each upvalue, `1` if it's a local of the enclosing function or
+    ///   `0` if it's one of its upvalues, followed by the local's slot or the upvalue's index
+    Closure,
+
+    /// 2 bytes instruction.
+    ///
+    /// Pushes the value of a variable captured by the current closure.
+    /// - 1: Opcode (`OP_GET_UPVALUE`)
+    /// - 2: Index of the upvalue
+    GetUpvalue,
+
+    /// 2 bytes instruction.
+    ///
+    /// Pops the value on top of the stack into a variable captured by the
+    /// current closure. Operands are the same as `GetUpvalue`.
+    SetUpvalue,
+
+    /// Single byte instruction.
+    ///
+    /// Moves the local on top of the stack into the upvalues capturing it, then
+    /// discards it.
+    CloseUpvalue,
 }
 
 impl fmt::Display for ByteCode {
@@ -53,7 +311,10 @@ pub struct Chunk {
 
     pub code: Vec<u8>,
     pub constants: Vec<ValueType>,
-    pub lines: HashMap<i32, Vec<usize>>,
+    /// Names of the global variables used by the chunk
+    pub identifiers: Vec<String>,
+    /// Source location of each byte in `code`
+    pub spans: Vec<Span>,
 }
 
 impl Chunk {
@@ -64,7 +325,8 @@ impl Chunk {
 
             code: Vec::new(),
             constants: Vec::new(),
-            lines: HashMap::new(),
+            identifiers: Vec::new(),
+            spans: Vec::new(),
         }
     }
 
@@ -83,52 +345,60 @@ impl Chunk {
         self.constants.len() - 1
     }
 
-    /// Associates the provided instruction index with the given line number
-    /// in the Chunk's line information. If the line number doesn't exist,
-    /// a new one is created.
-    fn write_line(&mut self, new_line: i32, instruction_index: usize) {
-        self.lines
-            .entry(new_line)
-            .or_default()
-            .push(instruction_index);
-    }
-
-    pub fn write_chunk(&mut self, byte: u8, new_line: i32) {
+    pub fn write_chunk(&mut self, byte: u8, span: Span) {
         if self.capacity < self.count + 1 {
             self.capacity = Chunk::grow_capacity(self.capacity);
         }
 
         self.code.push(byte);
-        self.write_line(new_line, self.code.len() - 1);
+        self.spans.push(span);
 
         self.count += 1;
     }
 
-    pub fn write_constant(&mut self, constant: ValueType, line: i32) {
+    pub fn write_constant(&mut self, constant: ValueType, span: Span) {
         let constant_index = self.add_constant(constant);
 
         if constant_index < 256 {
-            self.write_chunk(ByteCode::Constant as u8, line);
-            self.write_chunk(constant_index as u8, line);
+            self.write_chunk(ByteCode::Constant as u8, span);
+            self.write_chunk(constant_index as u8, span);
 
             return;
         }
 
-        self.write_chunk(ByteCode::Constant as u8, line);
-        self.write_chunk((constant_index & 0xFF) as u8, line); // Write Lower 8 bits
-        self.write_chunk(((constant_index >> 8) & 0xFF) as u8, line); // Write Next 8 bits
-        self.write_chunk(((constant_index >> 16) & 0xFF) as u8, line); // Write Upper 8 bits
+        self.write_chunk(ByteCode::ConstantLong as u8, span);
+        self.write_chunk((constant_index & 0xFF) as u8, span); // Write Lower 8 bits
+        self.write_chunk(((constant_index >> 8) & 0xFF) as u8, span); // Write Next 8 bits
+        self.write_chunk(((constant_index >> 16) & 0xFF) as u8, span); // Write Upper 8 bits
     }
 
-    /// Returns the line number of a given instructions index
-    pub fn get_line(&self, instruction_index: &usize) -> Option<&i32> {
-        for (line, instructions) in &self.lines {
-            if instructions.contains(instruction_index) {
-                return Some(line);
-            }
+    /// Index of the name in the identifiers table, adding it if it's not there
+    fn add_identifier(&mut self, name: &str) -> usize {
+        if let Some(index) = self
+            .identifiers
+            .iter()
+            .position(|identifier| identifier == name)
+        {
+            return index;
         }
 
-        None
+        self.identifiers.push(name.to_string());
+
+        self.identifiers.len() - 1
+    }
+
+    /// Writes an instruction that operates on the global variable with the given name
+    pub fn write_identifier(&mut self, operation: ByteCode, name: &str, span: Span) {
+        let identifier_index = self.add_identifier(name);
+
+        self.write_chunk(operation as u8, span);
+        self.write_chunk((identifier_index & 0xFF) as u8, span); // Write Lower 8 bits
+        self.write_chunk(((identifier_index >> 8) & 0xFF) as u8, span); // Write Upper 8 bits
+    }
+
+    /// Returns the line number of a given instructions index
+    pub fn get_line(&self, instruction_index: &usize) -> Option<&i32> {
+        self.spans.get(*instruction_index).map(|span| &span.line)
     }
 }
 
@@ -143,7 +413,7 @@ mod tests {
         let value = ValueType::Float(42.0);
         let instructions_count = 2;
 
-        chunk.write_constant(value, 1);
+        chunk.write_constant(value.clone(), Span::at_line(1));
 
         // Verify that the correct bytecode instructions are written
         assert_eq!(
@@ -174,12 +444,15 @@ mod tests {
 
         // Write small index constants
         for i in 0..small_const_size {
-            chunk.write_constant(ValueType::Int(i), 1);
+            chunk.write_constant(ValueType::Int(i), Span::at_line(1));
         }
 
         // Write and test large index constants
         for i in 0..4 {
-            chunk.write_constant(ValueType::Int(i as i32), 1);
+            chunk.write_constant(ValueType::Int(i), Span::at_line(1));
+
+            let instruction_index = chunk.code.len() - instructions_count as usize;
+            assert_eq!(chunk.code[instruction_index], ByteCode::ConstantLong as u8);
 
             assert_eq!(
                 chunk.count,
@@ -194,15 +467,15 @@ mod tests {
         let mut chunk = Chunk::new();
         let const_intruction_size = 2;
 
-        chunk.write_chunk(ByteCode::Return as u8, 123);
-        chunk.write_constant(ValueType::Float(1.2), 123);
-        chunk.write_constant(ValueType::Float(1.2), 123);
-        chunk.write_constant(ValueType::Float(1.2), 123);
-        chunk.write_constant(ValueType::Float(1.2), 128);
-        chunk.write_constant(ValueType::Float(1.2), 182);
+        chunk.write_chunk(ByteCode::Return as u8, Span::at_line(123));
+        chunk.write_constant(ValueType::Float(1.2), Span::at_line(123));
+        chunk.write_constant(ValueType::Float(1.2), Span::at_line(123));
+        chunk.write_constant(ValueType::Float(1.2), Span::at_line(123));
+        chunk.write_constant(ValueType::Float(1.2), Span::at_line(128));
+        chunk.write_constant(ValueType::Float(1.2), Span::at_line(182));
 
-        // Test line length
-        assert_eq!(chunk.lines.len(), 3);
+        // Test one span per byte
+        assert_eq!(chunk.spans.len(), chunk.code.len());
 
         // Test values
         assert_eq!(chunk.get_line(&0).unwrap(), &123); // test for OP_RETURN
@@ -1,7 +1,7 @@
 use crate::chunk::{ByteCode, Chunk};
 use crate::parser::lexer::Lexer;
 use crate::parser::tokens::Token;
-use crate::value::ValueType;
+use crate::value::{DataType, ValueType};
 
 fn constant_long_instruction(chunk: &Chunk, offset: usize) -> (String, usize) {
     // by combining the three bytes using `|`, we merge thenm into a single
@@ -10,12 +10,12 @@ fn constant_long_instruction(chunk: &Chunk, offset: usize) -> (String, usize) {
         | ((chunk.code[offset + 2] as u32) << 8) // mid byte
         | ((chunk.code[offset + 3] as u32) << 16); // highest byte
 
-    let constant_value: ValueType = chunk.constants[constant_index as usize];
+    let constant_value: &ValueType = &chunk.constants[constant_index as usize];
     let instruction_size = 4;
 
     (
         format!(
-            "OP_CONSTANT {:?} <- {:04} INDEX {:?}",
+            "OP_CONSTANT_LONG {:?} <- {:04} INDEX {:?}",
             constant_value,
             offset + 1,
             constant_index
@@ -30,7 +30,7 @@ fn simple_instruction(operation: &str, offset: usize) -> (String, usize) {
 
 fn constant_instruction(chunk: &Chunk, offset: usize) -> (String, usize) {
     let constant_index: u8 = chunk.code[offset + 1];
-    let constant_value: ValueType = chunk.constants[constant_index as usize];
+    let constant_value: &ValueType = &chunk.constants[constant_index as usize];
     let instruction_size = 2;
 
     (
@@ -44,6 +44,119 @@ fn constant_instruction(chunk: &Chunk, offset: usize) -> (String, usize) {
     )
 }
 
+fn identifier_instruction(operation: &str, chunk: &Chunk, offset: usize) -> (String, usize) {
+    let identifier_index =
+        (chunk.code[offset + 1] as usize) | ((chunk.code[offset + 2] as usize) << 8);
+    let instruction_size = 3;
+
+    (
+        format!(
+            "{} {:?} <- {:04} INDEX {:?}",
+            operation,
+            chunk.identifiers[identifier_index],
+            offset + 1,
+            identifier_index
+        ),
+        offset + instruction_size,
+    )
+}
+
+fn byte_instruction(operation: &str, chunk: &Chunk, offset: usize) -> (String, usize) {
+    let slot = chunk.code[offset + 1];
+    let instruction_size = 2;
+
+    (
+        format!("{} SLOT {:?}", operation, slot),
+        offset + instruction_size,
+    )
+}
+
+fn count_instruction(operation: &str, chunk: &Chunk, offset: usize) -> (String, usize) {
+    let count = chunk.code[offset + 1];
+    let instruction_size = 2;
+
+    (
+        format!("{} COUNT {:?}", operation, count),
+        offset + instruction_size,
+    )
+}
+
+fn jump_instruction(operation: &str, chunk: &Chunk, offset: usize) -> (String, usize) {
+    let jump = (chunk.code[offset + 1] as usize) | ((chunk.code[offset + 2] as usize) << 8);
+    let instruction_size = 3;
+
+    (
+        format!(
+            "{} {:04} -> {:04}",
+            operation,
+            offset,
+            offset + instruction_size + jump
+        ),
+        offset + instruction_size,
+    )
+}
+
+fn loop_instruction(operation: &str, chunk: &Chunk, offset: usize) -> (String, usize) {
+    let jump = (chunk.code[offset + 1] as usize) | ((chunk.code[offset + 2] as usize) << 8);
+    let instruction_size = 3;
+
+    (
+        format!(
+            "{} {:04} -> {:04}",
+            operation,
+            offset,
+            (offset + instruction_size).saturating_sub(jump)
+        ),
+        offset + instruction_size,
+    )
+}
+
+fn for_call_loop_instruction(chunk: &Chunk, offset: usize) -> (String, usize) {
+    let count = chunk.code[offset + 1];
+    let jump = (chunk.code[offset + 2] as usize) | ((chunk.code[offset + 3] as usize) << 8);
+    let instruction_size = 4;
+
+    (
+        format!(
+            "OP_FOR_CALL_LOOP COUNT {:?} {:04} -> {:04}",
+            count,
+            offset,
+            offset + instruction_size + jump
+        ),
+        offset + instruction_size,
+    )
+}
+
+fn closure_instruction(chunk: &Chunk, offset: usize) -> (String, usize) {
+    let count = chunk.code[offset + 1] as usize;
+    let mut text = format!("OP_CLOSURE COUNT {:?}", count);
+
+    for upvalue in 0..count {
+        let is_local = chunk.code[offset + 2 + upvalue * 2];
+        let index = chunk.code[offset + 3 + upvalue * 2];
+        let kind = if is_local == 1 { "local" } else { "upvalue" };
+
+        text.push_str(&format!(" | {} {:?}", kind, index));
+    }
+
+    (text, offset + 2 + count * 2)
+}
+
+fn type_instruction(chunk: &Chunk, offset: usize) -> (String, usize) {
+    let type_byte = chunk.code[offset + 1];
+    let data_type = DataType::all_variants().get((type_byte & !DataType::NULLABLE) as usize);
+    let nullable = match type_byte & DataType::NULLABLE {
+        0 => "",
+        _ => "?",
+    };
+    let instruction_size = 2;
+
+    (
+        format!("OP_CHECK_TYPE {:?}{}", data_type, nullable),
+        offset + instruction_size,
+    )
+}
+
 #[cfg(feature = "debug_trace_execution")]
 pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> (String, usize) {
     // Print lines info
@@ -74,11 +187,58 @@ pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> (String, usize)
             ByteCode::Return => simple_instruction("OP_RETURN", offset),
             ByteCode::Constant => constant_instruction(chunk, offset),
             ByteCode::ConstantLong => constant_long_instruction(chunk, offset),
+            ByteCode::Nil => simple_instruction("OP_NIL", offset),
+            ByteCode::True => simple_instruction("OP_TRUE", offset),
+            ByteCode::False => simple_instruction("OP_FALSE", offset),
             ByteCode::Negate => simple_instruction("OP_NEGATE", offset),
             ByteCode::Add => simple_instruction("OP_ADD", offset),
             ByteCode::Subtract => simple_instruction("OP_SUBTRACT", offset),
             ByteCode::Multiply => simple_instruction("OP_MULTIPLY", offset),
             ByteCode::Divide => simple_instruction("OP_DIVIDE", offset),
+            ByteCode::Modulo => simple_instruction("OP_MODULO", offset),
+            ByteCode::Power => simple_instruction("OP_POWER", offset),
+            ByteCode::IntDivide => simple_instruction("OP_INT_DIVIDE", offset),
+            ByteCode::BitAnd => simple_instruction("OP_BIT_AND", offset),
+            ByteCode::BitOr => simple_instruction("OP_BIT_OR", offset),
+            ByteCode::BitXor => simple_instruction("OP_BIT_XOR", offset),
+            ByteCode::ShiftLeft => simple_instruction("OP_SHIFT_LEFT", offset),
+            ByteCode::ShiftRight => simple_instruction("OP_SHIFT_RIGHT", offset),
+            ByteCode::Concat => simple_instruction("OP_CONCAT", offset),
+            ByteCode::ToString => simple_instruction("OP_TO_STRING", offset),
+            ByteCode::Equal => simple_instruction("OP_EQUAL", offset),
+            ByteCode::Less => simple_instruction("OP_LESS", offset),
+            ByteCode::Greater => simple_instruction("OP_GREATER", offset),
+            ByteCode::Not => simple_instruction("OP_NOT", offset),
+            ByteCode::BitNot => simple_instruction("OP_BIT_NOT", offset),
+            ByteCode::Len => simple_instruction("OP_LEN", offset),
+            ByteCode::Print => simple_instruction("OP_PRINT", offset),
+            ByteCode::Pop => simple_instruction("OP_POP", offset),
+            ByteCode::PopN => count_instruction("OP_POPN", chunk, offset),
+            ByteCode::DefineGlobal => identifier_instruction("OP_DEFINE_GLOBAL", chunk, offset),
+            ByteCode::GetGlobal => identifier_instruction("OP_GET_GLOBAL", chunk, offset),
+            ByteCode::SetGlobal => identifier_instruction("OP_SET_GLOBAL", chunk, offset),
+            ByteCode::GetLocal => byte_instruction("OP_GET_LOCAL", chunk, offset),
+            ByteCode::SetLocal => byte_instruction("OP_SET_LOCAL", chunk, offset),
+            ByteCode::CheckType => type_instruction(chunk, offset),
+            ByteCode::NewTable => simple_instruction("OP_NEW_TABLE", offset),
+            ByteCode::InitField => simple_instruction("OP_INIT_FIELD", offset),
+            ByteCode::GetIndex => simple_instruction("OP_GET_INDEX", offset),
+            ByteCode::SetIndex => simple_instruction("OP_SET_INDEX", offset),
+            ByteCode::Jump => jump_instruction("OP_JUMP", chunk, offset),
+            ByteCode::JumpIfFalse => jump_instruction("OP_JUMP_IF_FALSE", chunk, offset),
+            ByteCode::Loop => loop_instruction("OP_LOOP", chunk, offset),
+            ByteCode::ForPrep => jump_instruction("OP_FOR_PREP", chunk, offset),
+            ByteCode::ForLoop => loop_instruction("OP_FOR_LOOP", chunk, offset),
+            ByteCode::ForCall => simple_instruction("OP_FOR_CALL", offset),
+            ByteCode::ForCallLoop => for_call_loop_instruction(chunk, offset),
+            ByteCode::Call => count_instruction("OP_CALL", chunk, offset),
+            ByteCode::RefLocal => byte_instruction("OP_REF_LOCAL", chunk, offset),
+            ByteCode::RefGlobal => identifier_instruction("OP_REF_GLOBAL", chunk, offset),
+            ByteCode::RefUpvalue => byte_instruction("OP_REF_UPVALUE", chunk, offset),
+            ByteCode::Closure => closure_instruction(chunk, offset),
+            ByteCode::GetUpvalue => byte_instruction("OP_GET_UPVALUE", chunk, offset),
+            ByteCode::SetUpvalue => byte_instruction("OP_SET_UPVALUE", chunk, offset),
+            ByteCode::CloseUpvalue => simple_instruction("OP_CLOSE_UPVALUE", offset),
         };
     }
 
@@ -108,9 +268,9 @@ pub fn _disassemble_lexer<R: std::io::Read>(lexer: &mut Lexer<R>, name: &str) {
     println!("==== Lexer {:?} Disassemble ====", name);
     println!("LINE | TOKEN");
 
-    let mut previus_line = lexer.current_line;
+    let mut previus_line = 0;
     loop {
-        let token = lexer
+        let (token, span) = lexer
             .advance()
             .unwrap_or_else(|error| panic!("lexer failed: {:?}", error));
 
@@ -119,15 +279,13 @@ pub fn _disassemble_lexer<R: std::io::Read>(lexer: &mut Lexer<R>, name: &str) {
             break;
         }
 
-        match previus_line {
-            // lex line starts from 0.
-            0 => print!("{:04} ", lexer.current_line + 1),
-
-            _ if previus_line == lexer.current_line + 1 => print!("  |  "),
-            _ => print!("{:04} ", lexer.current_line + 1),
+        if span.line == previus_line {
+            print!("  |  ");
+        } else {
+            print!("{:04} ", span.line);
         }
 
-        previus_line = lexer.current_line + 1;
+        previus_line = span.line;
         println!("{:?}", token);
     }
 }
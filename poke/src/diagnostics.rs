@@ -0,0 +1,433 @@
+// Error reports in the style of the Rust and Gleam compilers
+// LINK - https://github.com/gleam-lang/gleam/blob/main/compiler-core/src/diagnostic.rs
+
+use crate::{
+    parser::{
+        errors::{LexicalError, LexicalErrorType, ParseError, ParseErrorType},
+        tokens::Span,
+    },
+    vm::{RuntimeError, RuntimeErrorType},
+};
+use std::{
+    borrow::Cow,
+    env,
+    io::{self, IsTerminal},
+    str,
+};
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    /// No ANSI escape codes, for logs and files
+    Plain,
+    /// ANSI colored output, for terminals
+    Colored,
+}
+
+impl ColorMode {
+    /// Colored when writing to a terminal, unless `NO_COLOR` is set
+    pub fn detect() -> Self {
+        if io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none() {
+            return ColorMode::Colored;
+        }
+
+        ColorMode::Plain
+    }
+
+    fn paint(self, color: &str, text: &str) -> String {
+        match self {
+            ColorMode::Plain => text.to_string(),
+            ColorMode::Colored => format!("{color}{text}{RESET}"),
+        }
+    }
+}
+
+/// Source code as shown in the snippets. Like a lossy conversion, but every
+/// invalid byte is replaced by a single `?`, so the spans still point to the
+/// same offsets.
+pub fn source_text(source: &[u8]) -> Cow<'_, str> {
+    if let Ok(text) = str::from_utf8(source) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut text = String::with_capacity(source.len());
+    for chunk in source.utf8_chunks() {
+        text.push_str(chunk.valid());
+        text.extend(chunk.invalid().iter().map(|_| '?'));
+    }
+
+    Cow::Owned(text)
+}
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    /// Identifies the kind of error, e.g. `E0101`
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    /// Renders the error with a snippet of the source code underlining its span
+    ///
+    /// ```text
+    /// error[E0101]: expected expression
+    ///  --> main.poke:2:3
+    ///   |
+    /// 2 |   )
+    ///   |   ^
+    /// ```
+    pub fn render(&self, file_name: &str, source: &str, mode: ColorMode) -> String {
+        let mut output = format!(
+            "{}{}\n",
+            mode.paint(RED, &format!("error[{}]", self.code)),
+            mode.paint(BOLD, &format!(": {}", self.message)),
+        );
+
+        let snippet = Snippet::new(source, self.span);
+        let line_number = snippet
+            .as_ref()
+            .map_or(String::new(), |_| self.span.line.to_string());
+        let padding = " ".repeat(line_number.len());
+        let gutter = mode.paint(BLUE, &format!("{padding} |"));
+
+        match snippet {
+            Some(snippet) => {
+                output.push_str(&format!(
+                    "{padding}{} {file_name}:{}:{}\n",
+                    mode.paint(BLUE, "-->"),
+                    self.span.line,
+                    self.span.column
+                ));
+                output.push_str(&format!("{gutter}\n"));
+                output.push_str(&format!(
+                    "{} {}\n",
+                    mode.paint(BLUE, &format!("{line_number} |")),
+                    snippet.text
+                ));
+                output.push_str(&format!(
+                    "{gutter} {}{}\n",
+                    snippet.indent,
+                    mode.paint(RED, &"^".repeat(snippet.width))
+                ));
+            }
+            None => output.push_str(&format!("{} {file_name}\n", mode.paint(BLUE, "-->"))),
+        }
+
+        if let Some(help) = &self.help {
+            output.push_str(&format!(
+                "{padding} {} {help}\n",
+                mode.paint(CYAN, "= help:")
+            ));
+        }
+
+        output
+    }
+}
+
+/// The source line a span starts at
+struct Snippet<'a> {
+    text: &'a str,
+    /// Whitespace up to the start of the span, keeping tabs so carets line up
+    indent: String,
+    /// Amount of characters underlined
+    width: usize,
+}
+
+impl<'a> Snippet<'a> {
+    fn new(source: &'a str, span: Span) -> Option<Self> {
+        if span.line < 1 || span.column < 1 {
+            return None;
+        }
+
+        // NOTE - Columns count characters, so the line is found by its newline
+        let line_start = source
+            .get(..span.start)?
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+        let line = source.get(line_start..)?;
+        let line = &line[..line.find('\n').unwrap_or(line.len())];
+
+        let prefix = line.get(..span.start - line_start)?;
+        let underlined = line
+            .get(span.start - line_start..span.end.saturating_sub(line_start).min(line.len()))
+            .unwrap_or("");
+
+        Some(Snippet {
+            text: line.trim_end_matches('\r'),
+            indent: prefix
+                .chars()
+                .map(|character| if character == '\t' { '\t' } else { ' ' })
+                .collect(),
+            width: underlined.chars().count().max(1),
+        })
+    }
+}
+
+impl From<&LexicalError> for Diagnostic {
+    fn from(error: &LexicalError) -> Self {
+        let (code, help) = match &error.error {
+            LexicalErrorType::UnexpectedToken { .. } => ("E0001", None),
+            LexicalErrorType::BadStringEscape => (
+                "E0002",
+                Some("valid escapes are \\a \\b \\f \\n \\r \\t \\v \\\\ \\\" \\' \\{ \\xXX \\ddd and \\u{XXXX}"),
+            ),
+            LexicalErrorType::BadHexEscape => {
+                ("E0003", Some("write exactly two hex digits, like \\x41"))
+            }
+            LexicalErrorType::DecimalEscapeTooLarge { .. } => {
+                ("E0004", Some("decimal escapes go from \\0 to \\255"))
+            }
+            LexicalErrorType::UnexpectedStringEnd => (
+                "E0005",
+                Some("close the string with the same quote it was opened with"),
+            ),
+            LexicalErrorType::IntegerOverflow { .. } => (
+                "E0006",
+                Some("ints are 32 bits long and bytes go from 0 to 255"),
+            ),
+            LexicalErrorType::MalformedNumber { .. } => ("E0007", None),
+            LexicalErrorType::Io { .. } => ("E0008", None),
+            LexicalErrorType::UnterminatedInterpolation => (
+                "E0009",
+                Some("close the interpolation with `}`, or write `\\{` for a literal brace"),
+            ),
+            LexicalErrorType::BadUnicodeEscape => (
+                "E0010",
+                Some("write up to six hex digits between braces, like \\u{E9}"),
+            ),
+            LexicalErrorType::InvalidUtf8 => ("E0011", Some("source files must be UTF-8 encoded")),
+            LexicalErrorType::InvalidLongBracket => (
+                "E0012",
+                Some("long strings open with `[[`, or `[` and `=`s and `[`, like `[==[`"),
+            ),
+            LexicalErrorType::UnterminatedLongString => (
+                "E0013",
+                Some("close the string with `]`, as many `=`s as it was opened with and `]`"),
+            ),
+            LexicalErrorType::UnterminatedComment => (
+                "E0014",
+                Some("close the comment with `]`, as many `=`s as it was opened with and `]`"),
+            ),
+            LexicalErrorType::EmptyInterpolation => (
+                "E0015",
+                Some("write an expression between the braces, or `\\{` for a literal brace"),
+            ),
+        };
+
+        Diagnostic {
+            code,
+            message: error.error.to_string(),
+            span: error.span,
+            help: help.map(str::to_string),
+        }
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        let (code, help) = match &error.error {
+            ParseErrorType::LexError { error } => return Diagnostic::from(error),
+            ParseErrorType::UnexpectedToken { .. } => ("E0100", None),
+            ParseErrorType::ExpectedExpression => ("E0101", None),
+            ParseErrorType::ExpectedToken { .. } => ("E0102", None),
+            ParseErrorType::ExpectedIdentifier { .. } => ("E0103", None),
+            ParseErrorType::ExpectedType { .. } => (
+                "E0104",
+                Some("available types are int, float, byte, bool, string and table"),
+            ),
+            ParseErrorType::UndeclaredVariable { .. } => (
+                "E0105",
+                Some("variables must be declared before use, like `int a = 1`"),
+            ),
+            ParseErrorType::AlreadyDeclared { .. } => ("E0106", None),
+            ParseErrorType::ImmutableAssignment { .. } => (
+                "E0107",
+                Some("make the variable mutable by declaring it with `mut`"),
+            ),
+            ParseErrorType::TooManyLocals => (
+                "E0108",
+                Some("a function can't have more than 256 locals alive at once"),
+            ),
+            ParseErrorType::JumpTooLarge => (
+                "E0109",
+                Some("branches can't be longer than 65535 bytes of bytecode"),
+            ),
+            ParseErrorType::BreakOutsideLoop => ("E0110", None),
+            ParseErrorType::TooManyArguments => ("E0111", None),
+            ParseErrorType::UnexpectedReturnValue => (
+                "E0112",
+                Some("only functions with a return type, like `-> int`, return values"),
+            ),
+            ParseErrorType::ExpectedPlace => (
+                "E0113",
+                Some("`mut` parameters assign to the variable given, like `mutate a`"),
+            ),
+            ParseErrorType::ImmutableArgument { .. } => (
+                "E0114",
+                Some("make the variable mutable by declaring it with `mut`"),
+            ),
+            ParseErrorType::MismatchedReference { .. } => (
+                "E0115",
+                Some("the variable must have the exact type of the parameter"),
+            ),
+            ParseErrorType::TooManyUpvalues => ("E0116", None),
+            ParseErrorType::MissingReturnValue { .. } => (
+                "E0117",
+                Some("functions with a return type must return a value of that type"),
+            ),
+            ParseErrorType::ForwardMutableCall { .. } => (
+                "E0118",
+                Some("declare the function before the functions calling it"),
+            ),
+        };
+
+        Diagnostic {
+            code,
+            message: error.error.to_string(),
+            span: error.span,
+            help: help.map(str::to_string),
+        }
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Self {
+        let code = match &error.error {
+            RuntimeErrorType::StackUnderflow => "E0200",
+            RuntimeErrorType::UnknownInstruction { .. } => "E0201",
+            RuntimeErrorType::TypeError { .. } => "E0202",
+            RuntimeErrorType::Io { .. } => "E0203",
+            RuntimeErrorType::MismatchedTypes { .. } => "E0204",
+            RuntimeErrorType::UndefinedVariable { .. } => "E0205",
+            RuntimeErrorType::ZeroForStep => "E0206",
+            RuntimeErrorType::NotCallable { .. } => "E0207",
+            RuntimeErrorType::ArityMismatch { .. } => "E0208",
+            RuntimeErrorType::StackOverflow => "E0209",
+            RuntimeErrorType::ExpectedReference { .. } => "E0210",
+            RuntimeErrorType::ArithmeticOverflow { .. } => "E0211",
+            RuntimeErrorType::DivisionByZero => "E0212",
+            RuntimeErrorType::NotIndexable { .. } => "E0213",
+            RuntimeErrorType::InvalidKey { .. } => "E0214",
+        };
+
+        Diagnostic {
+            code,
+            message: error.error.to_string(),
+            span: error.span,
+            help: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, parser::parser::Parser};
+
+    #[test]
+    fn test_render_parse_error() {
+        let source = "1 +\n  )";
+        let errors = Parser::new(&mut Chunk::new())
+            .load(source.as_bytes())
+            .expect_err("missing operand should fail");
+
+        let rendered = Diagnostic::from(&errors[0]).render("main.poke", source, ColorMode::Plain);
+
+        assert_eq!(
+            rendered,
+            "error[E0101]: expected expression\n \
+             --> main.poke:2:3\n  \
+               |\n\
+             2 |   )\n  \
+               |   ^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_lex_error_with_help() {
+        let source = "10 +\n\t0x1_0000_0000 * 2";
+        let errors = Parser::new(&mut Chunk::new())
+            .load(source.as_bytes())
+            .expect_err("overflowing literal should fail");
+
+        let rendered = Diagnostic::from(&errors[0]).render("main.poke", source, ColorMode::Plain);
+
+        assert_eq!(
+            rendered,
+            "error[E0006]: number 0x100000000 is too large for its type\n \
+             --> main.poke:2:2\n  \
+               |\n\
+             2 | \t0x1_0000_0000 * 2\n  \
+               | \t^^^^^^^^^^^^^\n  \
+               = help: ints are 32 bits long and bytes go from 0 to 255\n"
+        );
+    }
+
+    #[test]
+    fn test_render_after_unicode() {
+        let source = "print 'maçã' + )";
+        let errors = Parser::new(&mut Chunk::new())
+            .load(source.as_bytes())
+            .expect_err("missing operand should fail");
+
+        let rendered = Diagnostic::from(&errors[0]).render("main.poke", source, ColorMode::Plain);
+
+        assert_eq!(
+            rendered,
+            "error[E0101]: expected expression\n \
+             --> main.poke:1:16\n  \
+               |\n\
+             1 | print 'maçã' + )\n  \
+               |                ^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_invalid_utf8() {
+        let source = b"int a = 1\nprint '\xffa\xc3'";
+        let errors = Parser::new(&mut Chunk::new())
+            .load(&source[..])
+            .expect_err("invalid UTF-8 should fail");
+
+        let rendered = Diagnostic::from(&errors[0]).render(
+            "main.poke",
+            &source_text(source),
+            ColorMode::Plain,
+        );
+
+        assert_eq!(
+            rendered,
+            "error[E0011]: invalid UTF-8 sequence\n \
+             --> main.poke:2:7\n  \
+               |\n\
+             2 | print '?a?'\n  \
+               |       ^^^^^\n  \
+               = help: source files must be UTF-8 encoded\n"
+        );
+    }
+
+    #[test]
+    fn test_render_colored_and_without_location() {
+        let diagnostic = Diagnostic {
+            code: "E0200",
+            message: "stack underflow".to_string(),
+            span: Span::default(),
+            help: None,
+        };
+
+        assert_eq!(
+            diagnostic.render("main.poke", "", ColorMode::Plain),
+            "error[E0200]: stack underflow\n--> main.poke\n"
+        );
+
+        let colored = diagnostic.render("main.poke", "", ColorMode::Colored);
+        assert!(colored.starts_with("\x1b[1;31merror[E0200]\x1b[0m"));
+    }
+}
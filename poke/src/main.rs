@@ -1,33 +1,67 @@
 mod chunk;
 mod debug;
+mod diagnostics;
 mod parser;
 mod value;
 mod vm;
 use chunk::Chunk;
+use diagnostics::{ColorMode, Diagnostic};
 use parser::parser::Parser;
-use std::{env, fs::File, io::BufReader};
-use vm::VirtualMachine;
+use std::{env, fs, process};
+use vm::{InterpretError, VirtualMachine, FRAMES_MAX};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: {} script", args[0]);
+    let (file_name, max_depth) = match args.as_slice() {
+        [_, file_name] => (file_name, FRAMES_MAX),
+        [_, file_name, flag, depth] if flag == "--max-depth" => match depth.parse() {
+            Ok(depth) => (file_name, depth),
+            Err(_) => {
+                eprintln!("Invalid max depth: {depth}");
 
-        return;
-    }
+                process::exit(64)
+            }
+        },
+        _ => {
+            println!("Usage: {} script [--max-depth <calls>]", args[0]);
+
+            return;
+        }
+    };
 
-    let file = File::open(&args[1]).expect("poke file");
+    // NOTE - Read as bytes, the lexer reports invalid UTF-8 where it's found
+    let source = fs::read(file_name).unwrap_or_else(|error| {
+        eprintln!("Failed to read {file_name}: {error}");
+
+        process::exit(74)
+    });
+    let source_text = diagnostics::source_text(&source);
+
+    let report = |diagnostic: Diagnostic| {
+        eprint!(
+            "{}",
+            diagnostic.render(file_name, &source_text, ColorMode::detect())
+        )
+    };
 
     // ANCHOR - "Compiling proccess": If no error encountered, take user's program
     // and fill it with bytecode, so it can be executed by the VM
     let mut chunk = Chunk::new();
-    let _ = Parser::new(&mut chunk)
-        .load(BufReader::new(file))
-        .unwrap_or_else(|err| panic!("Failed to parse chunk: {:?}", err));
+    if let Err(errors) = Parser::new(&mut chunk).load(&source[..]) {
+        for error in &errors {
+            report(Diagnostic::from(error));
+        }
+
+        process::exit(65);
+    }
 
-    let mut vm = VirtualMachine::new(&chunk);
+    let mut vm = VirtualMachine::new(&chunk).with_max_depth(max_depth);
     match vm.run_interpreter() {
         Ok(_) => println!("VM executed succesfully"),
-        Err(error) => panic!("VM failed {:?}", error),
+        Err(InterpretError::RuntimeError(error)) => {
+            report(Diagnostic::from(&error));
+
+            process::exit(70);
+        }
     };
 }
@@ -1,33 +1,210 @@
-use super::tokens::Token;
+use super::tokens::{Span, Token};
+use crate::value::DataType;
+use std::{fmt, io};
 
 #[derive(Debug)]
 pub enum LexicalErrorType {
     BadStringEscape,
+    BadHexEscape,
+    BadUnicodeEscape,
+    DecimalEscapeTooLarge { value: u32 },
     UnexpectedStringEnd,
+    UnterminatedInterpolation,
+    EmptyInterpolation,
+    InvalidLongBracket,
+    UnterminatedLongString,
+    UnterminatedComment,
     UnexpectedToken { token: char },
+    InvalidUtf8,
+    IntegerOverflow { number: String },
+    MalformedNumber { number: String },
+    Io { error: io::Error },
 }
 
 #[derive(Debug)]
 pub struct LexicalError {
     pub error: LexicalErrorType,
-    pub line: i32,
+    pub span: Span,
+}
+
+impl fmt::Display for LexicalErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexicalErrorType::BadStringEscape => write!(f, "invalid escape sequence"),
+            LexicalErrorType::BadHexEscape => {
+                write!(f, "hexadecimal escape must have two hex digits (\\xXX)")
+            }
+            LexicalErrorType::BadUnicodeEscape => {
+                write!(f, "unicode escape must be a valid code point (\\u{{XXXX}})")
+            }
+            LexicalErrorType::DecimalEscapeTooLarge { value } => {
+                write!(f, "decimal escape \\{value} is larger than 255")
+            }
+            LexicalErrorType::UnexpectedStringEnd => write!(f, "unterminated string"),
+            LexicalErrorType::UnterminatedInterpolation => {
+                write!(f, "unterminated string interpolation")
+            }
+            LexicalErrorType::EmptyInterpolation => write!(f, "expected expression"),
+            LexicalErrorType::InvalidLongBracket => write!(f, "invalid long string delimiter"),
+            LexicalErrorType::UnterminatedLongString => write!(f, "unterminated long string"),
+            LexicalErrorType::UnterminatedComment => write!(f, "unterminated block comment"),
+            LexicalErrorType::UnexpectedToken { token } => {
+                write!(f, "unexpected character {token:?}")
+            }
+            LexicalErrorType::InvalidUtf8 => write!(f, "invalid UTF-8 sequence"),
+            LexicalErrorType::IntegerOverflow { number } => {
+                write!(f, "number {number} is too large for its type")
+            }
+            LexicalErrorType::MalformedNumber { number } => write!(f, "malformed number {number}"),
+            LexicalErrorType::Io { error } => write!(f, "failed to read input: {error}"),
+        }
+    }
+}
+
+impl fmt::Display for LexicalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.error, self.span.line, self.span.column
+        )
+    }
 }
 
 #[derive(Debug)]
 pub enum ParseErrorType {
-    LexError { error: LexicalError },
-    UnexpectedToken { token: Token },
+    LexError {
+        error: LexicalError,
+    },
+    UnexpectedToken {
+        token: Token,
+    },
+    ExpectedToken {
+        expected: Token,
+        found: Token,
+    },
     ExpectedExpression,
+    ExpectedIdentifier {
+        found: Token,
+    },
+    ExpectedType {
+        found: Token,
+    },
+    UndeclaredVariable {
+        name: String,
+    },
+    AlreadyDeclared {
+        name: String,
+    },
+    ImmutableAssignment {
+        name: String,
+    },
+    TooManyLocals,
+    JumpTooLarge,
+    BreakOutsideLoop,
+    TooManyArguments,
+    UnexpectedReturnValue,
+    MissingReturnValue {
+        expected: DataType,
+    },
+    ExpectedPlace,
+    ImmutableArgument {
+        name: String,
+    },
+    MismatchedReference {
+        name: String,
+        expected: DataType,
+        found: DataType,
+    },
+    TooManyUpvalues,
+    ForwardMutableCall {
+        name: String,
+    },
 }
 
 #[derive(Debug)]
 pub struct ParseError {
     pub error: ParseErrorType,
-    pub line: i32,
+    pub span: Span,
 }
 
 impl ParseError {
-    pub fn new(error: ParseErrorType, line: i32) -> Self {
-        ParseError { error, line }
+    pub fn new(error: ParseErrorType, span: Span) -> Self {
+        ParseError { error, span }
+    }
+}
+
+impl From<LexicalError> for ParseError {
+    fn from(error: LexicalError) -> Self {
+        let span = error.span;
+
+        ParseError::new(ParseErrorType::LexError { error }, span)
+    }
+}
+
+impl fmt::Display for ParseErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorType::LexError { error } => write!(f, "{}", error.error),
+            ParseErrorType::UnexpectedToken { token } => write!(f, "unexpected {token}"),
+            ParseErrorType::ExpectedToken { expected, found } => {
+                write!(f, "expected {expected}, found {found}")
+            }
+            ParseErrorType::ExpectedExpression => write!(f, "expected expression"),
+            ParseErrorType::ExpectedIdentifier { found } => {
+                write!(f, "expected identifier, found {found}")
+            }
+            ParseErrorType::ExpectedType { found } => write!(f, "expected type, found {found}"),
+            ParseErrorType::UndeclaredVariable { name } => {
+                write!(f, "cannot find variable `{name}` in this scope")
+            }
+            ParseErrorType::AlreadyDeclared { name } => {
+                write!(f, "variable `{name}` is already declared")
+            }
+            ParseErrorType::ImmutableAssignment { name } => {
+                write!(f, "cannot assign twice to immutable variable `{name}`")
+            }
+            ParseErrorType::TooManyLocals => write!(f, "too many local variables in scope"),
+            ParseErrorType::JumpTooLarge => write!(f, "too much code to jump over"),
+            ParseErrorType::BreakOutsideLoop => write!(f, "`break` outside of a loop"),
+            ParseErrorType::TooManyArguments => {
+                write!(f, "functions can't take more than 255 arguments")
+            }
+            ParseErrorType::UnexpectedReturnValue => write!(f, "unexpected return value"),
+            ParseErrorType::MissingReturnValue { expected } => {
+                write!(f, "missing return value of type {expected}")
+            }
+            ParseErrorType::TooManyUpvalues => {
+                write!(f, "functions can't capture more than 255 variables")
+            }
+            ParseErrorType::ExpectedPlace => {
+                write!(f, "expected a variable as argument of a `mut` parameter")
+            }
+            ParseErrorType::ImmutableArgument { name } => {
+                write!(f, "cannot pass immutable variable `{name}` as mutable")
+            }
+            ParseErrorType::MismatchedReference {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "mismatched types: `mut` parameter takes {expected}, `{name}` is {found}"
+            ),
+            ParseErrorType::ForwardMutableCall { name } => write!(
+                f,
+                "cannot call `{name}` before its declaration, it has `mut` parameters"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.error, self.span.line, self.span.column
+        )
     }
 }
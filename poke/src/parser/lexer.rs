@@ -7,12 +7,12 @@
 mod tests;
 
 use super::errors::{LexicalError, LexicalErrorType};
-use crate::parser::tokens::Token;
-use core::panic;
+use crate::parser::tokens::{Span, Spanned, Token};
 use std::{
-    io::{Bytes, Read},
+    io::{BufReader, Bytes, Read},
     iter::Peekable,
-    mem,
+    num::{IntErrorKind, ParseIntError},
+    str,
 };
 
 pub type LexResult = Result<Token, LexicalError>;
@@ -20,51 +20,103 @@ pub type LexResult = Result<Token, LexicalError>;
 // LINK - https://github.com/gleam-lang/gleam/blob/main/compiler-core/src/parse/lexer.rs#L19
 pub struct Lexer<R: Read> {
     pub current_line: i32,
+    pub current_column: i32,
+
+    input: Peekable<Bytes<BufReader<R>>>,
+    ahead: Option<Spanned>,
+    /// Set once reading the input fails, from then on the input is considered over
+    input_failed: bool,
+
+    /// Amount of bytes read from the input stream
+    offset: usize,
+    /// Span of the token being lexed, `end` is only set once it's finished
+    token_start: Span,
+    /// Interpolations being lexed, the innermost one last
+    interpolations: Vec<Interpolation>,
+}
 
-    input: Peekable<Bytes<R>>,
-    ahead: Token,
+/// A `{` inside a string, whose expression is lexed as normal tokens until
+/// the matching `}` resumes the string
+struct Interpolation {
+    quote_character: u8,
+    /// Amount of `{` opened by the expression and not closed yet
+    depth: usize,
+    /// Span from the start of the string segment up to the `{`
+    span: Span,
 }
 
 impl<R: Read> Lexer<R> {
     pub fn new(input: R) -> Self {
         Lexer {
-            input: input.bytes().peekable(),
-            ahead: Token::EoS,
+            input: BufReader::new(input).bytes().peekable(),
+            ahead: None,
+            input_failed: false,
             current_line: 0,
+            current_column: 0,
+            offset: 0,
+            token_start: Span::default(),
+            interpolations: Vec::new(),
         }
     }
 
-    pub fn peek(&mut self) -> Result<&Token, LexicalError> {
-        if self.ahead == Token::EoS {
-            self.ahead = self.advance()?;
+    pub fn peek(&mut self) -> Result<&Spanned, LexicalError> {
+        if self.ahead.is_none() {
+            self.ahead = Some(self.advance()?);
         }
 
-        Ok(&self.ahead)
+        Ok(self.ahead.as_ref().unwrap())
     }
 
     #[cfg(test)]
     pub fn expect(&mut self, expected_token: Token) {
-        if let Ok(token) = self.advance() {
-            assert_eq!(token, expected_token);
+        match self.advance() {
+            Ok((token, _)) => assert_eq!(token, expected_token),
+            Err(error) => panic!("expected {:?}, found error {:?}", expected_token, error),
+        }
+    }
+
+    pub fn advance(&mut self) -> Result<Spanned, LexicalError> {
+        // If there's a token stored in ahead, it was already peeked, so it returns
+        // that token. Otherwise, it fetches the next token from the input stream.
+        if let Some(spanned) = self.ahead.take() {
+            return Ok(spanned);
         }
 
-        panic!("No token found")
+        let token = self.lex_token()?;
+
+        Ok((token, self.token_span()))
     }
 
-    pub fn advance(&mut self) -> LexResult {
-        // If ahead is not Token::EoS, it means that the next token is already
-        // stored in ahead, so it returns that token. Otherwise, it fetches the
-        // next token from the input stream and returns it.
-        if self.ahead != Token::EoS {
-            return Ok(mem::replace(&mut self.ahead, Token::EoS));
+    fn lex_token(&mut self) -> LexResult {
+        // NOTE - Loops instead of recursing, so a long run of comments or
+        // blank lines can't overflow the stack
+        loop {
+            if let Some(token) = self.lex_token_or_trivia()? {
+                return Ok(token);
+            }
         }
+    }
 
-        let byte_char = self.next_byte_char();
+    /// Lexes the next token, or returns `None` after skipping blank spaces or
+    /// a comment
+    fn lex_token_or_trivia(&mut self) -> Result<Option<Token>, LexicalError> {
+        self.token_start = Span {
+            start: self.offset,
+            end: self.offset,
+            line: self.current_line + 1,
+            column: self.current_column + 1,
+        };
+
+        let byte_char = self.next_byte_char()?;
         if byte_char.is_none() {
-            return Ok(Token::EoS);
+            if let Some(error) = self.close_interpolations() {
+                return Err(error);
+            }
+
+            return Ok(Some(Token::EoS));
         }
 
-        match byte_char.unwrap() {
+        let token = match byte_char.unwrap() {
             // ANCHOR - Symbols
             b'+' => Ok(Token::Add),
             b'*' => Ok(Token::Mul),
@@ -75,16 +127,23 @@ impl<R: Read> Lexer<R> {
             b'|' => Ok(Token::BitOr),
             b'(' => Ok(Token::ParL),
             b')' => Ok(Token::ParR),
-            b'{' => Ok(Token::CurlyL),
-            b'}' => Ok(Token::CurlyR),
-            b'[' => Ok(Token::SqurL),
+            b'{' => {
+                if let Some(interpolation) = self.interpolations.last_mut() {
+                    interpolation.depth += 1;
+                }
+
+                Ok(Token::CurlyL)
+            }
+            b'}' => self.lex_curly_right(),
+            b'[' => self.lex_square_left(),
             b']' => Ok(Token::SqurR),
             b';' => Ok(Token::SemiColon),
             b',' => Ok(Token::Comma),
+            b'?' => Ok(Token::Question),
 
             b':' => self.check_ahead(b':', Token::Colon, Token::DoubColon),
             b'/' => self.check_ahead(b'/', Token::Div, Token::Idiv),
-            b'=' => self.check_ahead(b'=', Token::Equal, Token::Assign),
+            b'=' => self.check_ahead(b'=', Token::Assign, Token::Equal),
             b'~' => self.check_ahead(b'=', Token::BitNot, Token::NotEq),
 
             b'<' => self.check_ahead_multi_option(
@@ -101,83 +160,248 @@ impl<R: Read> Lexer<R> {
 
             // ANCHOR Complex symbols
             b'.' => self.lex_number_or_dots(),
-            b'-' => self.check_complex_ahead(vec![b'-', b'>'], Token::Sub, Lexer::lex_dash_symbol),
+            b'-' => match self.peek_byte_char() {
+                b'>' => {
+                    let _ = self.next_byte_char();
+
+                    Ok(Token::Arrow)
+                }
+                b'-' => {
+                    let _ = self.next_byte_char();
+                    self.skip_comment()?;
+
+                    return Ok(None);
+                }
+                _ => Ok(Token::Sub),
+            },
 
             // ANCHOR Strings
             b'\'' | b'"' => self.lex_string(byte_char.unwrap()),
 
             // ANCHOR - Numbers
             b'0'..=b'9' => self.lex_number(byte_char.unwrap()),
-            b'A'..=b'Z' | b'a'..=b'z' | b'_' => Ok(self.lex_identifier_or_name(byte_char.unwrap())),
+            b'A'..=b'Z' | b'a'..=b'z' | b'_' => {
+                self.lex_identifier_or_name(byte_char.unwrap() as char)
+            }
 
             // ANCHOR - Blank spaces
-            b' ' | b'\r' | b'\t' => self.advance(), // Ignore spaces
-            b'\n' => self.lex_next_line(),
+            b' ' | b'\r' | b'\t' => return Ok(None), // Ignore spaces
+
+            // NOTE - Interpolations end with their line, so a missing `}` is
+            // reported before the errors of the next lines
+            b'\n' => match self.close_interpolations() {
+                Some(error) => Err(error),
+                None => return Ok(None),
+            },
+
+            // ANCHOR - Unicode identifiers
+            0x80.. => {
+                let character = self.read_utf8_char(byte_char.unwrap())?;
+                if !is_identifier_start(character) {
+                    return Err(self.error(LexicalErrorType::UnexpectedToken { token: character }));
+                }
+
+                self.lex_identifier_or_name(character)
+            }
 
             // ANCHOR - INVALID
-            _ => Err(LexicalError {
-                error: LexicalErrorType::UnexpectedToken {
-                    token: byte_char.unwrap() as char,
-                },
+            _ => Err(self.error(LexicalErrorType::UnexpectedToken {
+                token: byte_char.unwrap() as char,
+            })),
+        };
+
+        token.map(Some)
+    }
 
-                line: self.current_line + 1,
-            }),
+    /// Span from the start of the current token up to the last character read
+    fn token_span(&self) -> Span {
+        Span {
+            end: self.offset,
+            ..self.token_start
         }
     }
 
-    /// Advances the interator and return the next character from input stream (as byte)
-    fn next_byte_char(&mut self) -> Option<u8> {
-        self.input.next().map(|byte_result| {
-            byte_result.unwrap_or_else(|error| {
-                panic!("(lexer) failed to iterate through input stream. {}", error)
-            })
-        })
+    /// Builds an error spanning the token being lexed
+    fn error(&self, error: LexicalErrorType) -> LexicalError {
+        LexicalError {
+            error,
+            span: self.token_span(),
+        }
+    }
+
+    /// Advances the interator and return the next character from input stream (as byte).
+    /// Also keeps track of the current line and column.
+    fn next_byte_char(&mut self) -> Result<Option<u8>, LexicalError> {
+        if self.input_failed {
+            return Ok(None);
+        }
+
+        let byte_char = match self.input.next() {
+            Some(Ok(byte_char)) => byte_char,
+            Some(Err(error)) => {
+                self.input_failed = true;
+
+                return Err(self.error(LexicalErrorType::Io { error }));
+            }
+            None => return Ok(None),
+        };
+
+        self.offset += 1;
+        if byte_char == b'\n' {
+            self.current_line += 1;
+            self.current_column = 0;
+        } else if !is_utf8_continuation(byte_char) {
+            // Columns count characters, not bytes
+            self.current_column += 1;
+        }
+
+        Ok(Some(byte_char))
     }
 
+    /// Reads the rest of the UTF-8 encoded character starting with the given byte
+    fn read_utf8_char(&mut self, first_byte: u8) -> Result<char, LexicalError> {
+        let width = match first_byte {
+            0x00..=0x7f => 1,
+            0xc2..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf4 => 4,
+            _ => return Err(self.error(LexicalErrorType::InvalidUtf8)),
+        };
+
+        let mut bytes = [first_byte, 0, 0, 0];
+        for byte in bytes.iter_mut().take(width).skip(1) {
+            if !is_utf8_continuation(self.peek_byte_char()) {
+                return Err(self.error(LexicalErrorType::InvalidUtf8));
+            }
+
+            *byte = self.next_byte_char()?.unwrap_or_default();
+        }
+
+        // NOTE - Also rejects overlong encodings and surrogates
+        str::from_utf8(&bytes[..width])
+            .ok()
+            .and_then(|text| text.chars().next())
+            .ok_or_else(|| self.error(LexicalErrorType::InvalidUtf8))
+    }
+
+    /// Returns the next character without consuming it, or `\0` at the end of the stream.
+    /// Errors are not consumed either, so the next call to `next_byte_char` reports them.
     fn peek_byte_char(&mut self) -> u8 {
         match self.input.peek() {
             Some(Ok(byte_char)) => *byte_char,
-            Some(_) => panic!("(lexer) failed to peek error"),
-            None => b'\0',
+            Some(Err(_)) | None => b'\0',
         }
     }
 
-    fn lex_next_line(&mut self) -> LexResult {
-        self.current_line += 1;
+    /// A `}` closing an interpolation resumes the string it's in
+    fn lex_curly_right(&mut self) -> LexResult {
+        match self.interpolations.last_mut() {
+            Some(interpolation) if interpolation.depth == 0 => {
+                let quote_character = interpolation.quote_character;
+                self.interpolations.pop();
+
+                self.lex_string(quote_character)
+            }
+            Some(interpolation) => {
+                interpolation.depth -= 1;
 
-        self.advance()
+                Ok(Token::CurlyR)
+            }
+            None => Ok(Token::CurlyR),
+        }
     }
 
-    // TODO - add string interpolation
+    /// A string running until the end of the input inside an interpolation,
+    /// like `"a {b "`, most likely means the interpolation wasn't closed
+    fn unterminated_string_error(&mut self) -> LexicalError {
+        self.close_interpolations()
+            .unwrap_or_else(|| self.error(LexicalErrorType::UnexpectedStringEnd))
+    }
+
+    /// Drops the interpolations left open, reporting the innermost one
+    fn close_interpolations(&mut self) -> Option<LexicalError> {
+        let innermost = self.interpolations.pop()?;
+        self.interpolations.clear();
+
+        Some(LexicalError {
+            error: LexicalErrorType::UnterminatedInterpolation,
+            span: innermost.span,
+        })
+    }
+
+    /// Lexes a string up to its closing quote, or up to a `{` starting an
+    /// interpolation, like in `"value is {a + 1}"`
     fn lex_string(&mut self, quote_character: u8) -> LexResult {
-        let mut buffer = String::new();
+        // NOTE - Kept as bytes since escapes can write any byte, the whole
+        // string is only checked to be valid UTF-8 at the end
+        let mut buffer = Vec::new();
 
         loop {
-            let next_byte = self.next_byte_char();
+            let next_byte = self.next_byte_char()?;
 
             if next_byte.is_none() {
-                return Err(LexicalError {
-                    error: LexicalErrorType::UnexpectedStringEnd,
-                    line: self.current_line + 1,
-                });
+                return Err(self.unterminated_string_error());
             }
 
             match next_byte.unwrap() {
-                b'\\' => buffer.push(self.read_scape()? as char), // Push escape
+                b'\\' => self.read_scape(&mut buffer)?, // Push escape
+                b'{' => {
+                    self.interpolations.push(Interpolation {
+                        quote_character,
+                        depth: 0,
+                        span: self.token_span(),
+                    });
+
+                    // NOTE - The `}` is left to resume the string, like after an expression
+                    if self.peek_byte_char() == b'}' {
+                        return Err(LexicalError {
+                            error: LexicalErrorType::EmptyInterpolation,
+                            span: Span {
+                                start: self.offset - 1,
+                                end: self.offset + 1,
+                                line: self.current_line + 1,
+                                column: self.current_column,
+                            },
+                        });
+                    }
+
+                    return Ok(Token::Interpolation {
+                        value: self.decode_string(buffer)?,
+                    });
+                }
 
                 character if character == quote_character => break, // Close string
-                character => buffer.push(character as char),        // Push character
+                character => buffer.push(character),                // Push character
             }
         }
 
-        Ok(Token::String { value: buffer })
+        Ok(Token::String {
+            value: self.decode_string(buffer)?,
+        })
+    }
+
+    fn decode_string(&self, buffer: Vec<u8>) -> Result<String, LexicalError> {
+        String::from_utf8(buffer).map_err(|_| self.error(LexicalErrorType::InvalidUtf8))
     }
 
-    fn read_scape(&mut self) -> Result<u8, LexicalError> {
+    fn read_scape(&mut self, buffer: &mut Vec<u8>) -> Result<(), LexicalError> {
         let next_byte = self
-            .next_byte_char()
-            .unwrap_or_else(|| panic!("(lexer) failed to get next byte from string escape"));
+            .next_byte_char()?
+            .ok_or_else(|| self.error(LexicalErrorType::UnexpectedStringEnd))?;
+
+        if next_byte == b'u' {
+            let character = self.read_unicode_escape()?;
+            buffer.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes());
+
+            return Ok(());
+        }
+
+        buffer.push(self.read_byte_escape(next_byte)?);
 
+        Ok(())
+    }
+
+    fn read_byte_escape(&mut self, next_byte: u8) -> Result<u8, LexicalError> {
         match next_byte {
             b'a' => Ok(0x07),
             b'b' => Ok(0x08),
@@ -190,31 +414,61 @@ impl<R: Read> Lexer<R> {
 
             b'\\' => Ok(b'\\'),
             b'\'' => Ok(b'\''),
+            b'{' => Ok(b'{'),
 
             b'x' => self.read_hexadecimal_escape(), // format: \xXX
             character @ b'0'..=b'9' => self.read_decimal_escape(character), // format: \d[d[d]]
 
-            _ => Err(LexicalError {
-                error: LexicalErrorType::BadStringEscape,
-                line: self.current_line + 1,
-            }),
+            _ => Err(self.error(LexicalErrorType::BadStringEscape)),
         }
     }
 
+    /// format: \u{XXXX}, with one to six hex digits
+    fn read_unicode_escape(&mut self) -> Result<char, LexicalError> {
+        if self.next_byte_char()? != Some(b'{') {
+            return Err(self.error(LexicalErrorType::BadUnicodeEscape));
+        }
+
+        let mut value: u32 = 0;
+        let mut digits = 0;
+
+        loop {
+            let byte_char = self
+                .next_byte_char()?
+                .ok_or_else(|| self.error(LexicalErrorType::BadUnicodeEscape))?;
+
+            if byte_char == b'}' && digits > 0 {
+                break;
+            }
+
+            let hex_digit = char::to_digit(byte_char as char, 16)
+                .filter(|_| digits < 6)
+                .ok_or_else(|| self.error(LexicalErrorType::BadUnicodeEscape))?;
+
+            value = value * 16 + hex_digit;
+            digits += 1;
+        }
+
+        char::from_u32(value).ok_or_else(|| self.error(LexicalErrorType::BadUnicodeEscape))
+    }
+
     fn read_hexadecimal_escape(&mut self) -> Result<u8, LexicalError> {
-        let hex_digit_1 =
-            char::to_digit(self.next_byte_char().expect("invalid format") as char, 16)
-                .expect("correct \\x format: first hex digit");
-        let hex_digit_2 =
-            char::to_digit(self.next_byte_char().expect("invalid format") as char, 16)
-                .expect("correct \\x format: second hex digit");
+        let mut value = 0;
 
-        Ok((hex_digit_1 * 16 + hex_digit_2) as u8)
+        for _ in 0..2 {
+            let hex_digit = self
+                .next_byte_char()?
+                .and_then(|byte_char| char::to_digit(byte_char as char, 16))
+                .ok_or_else(|| self.error(LexicalErrorType::BadHexEscape))?;
+
+            value = value * 16 + hex_digit;
+        }
+
+        Ok(value as u8)
     }
 
     fn read_decimal_escape(&mut self, character: u8) -> Result<u8, LexicalError> {
-        let mut decimal_value = char::to_digit(character as char, 10)
-            .unwrap_or_else(|| panic!("(lexer) failed to convert char to digit: {:?}", character));
+        let mut decimal_value = (character - b'0') as u32;
 
         if let Some(digit) = char::to_digit(self.peek_byte_char() as char, 10) {
             let _ = self.next_byte_char();
@@ -227,7 +481,11 @@ impl<R: Read> Lexer<R> {
             }
         }
 
-        Ok(u8::try_from(decimal_value).expect("decimal escape too large"))
+        u8::try_from(decimal_value).map_err(|_| {
+            self.error(LexicalErrorType::DecimalEscapeTooLarge {
+                value: decimal_value,
+            })
+        })
     }
 
     fn lex_number(&mut self, current_byte: u8) -> LexResult {
@@ -237,7 +495,6 @@ impl<R: Read> Lexer<R> {
             // Hex
             if next_byte == b'x' || next_byte == b'X' {
                 let _ = self.next_byte_char();
-                let _ = self.next_byte_char();
 
                 return self.lex_number_radix(16, "0x");
             }
@@ -245,14 +502,13 @@ impl<R: Read> Lexer<R> {
             // Octal
             if next_byte == b'o' || next_byte == b'O' {
                 let _ = self.next_byte_char();
-                let _ = self.next_byte_char();
 
                 return self.lex_number_radix(8, "0o");
             }
 
+            // Binary
             if next_byte == b'b' || next_byte == b'B' {
                 let _ = self.next_byte_char();
-                let _ = self.next_byte_char();
 
                 return self.lex_number_radix(2, "0b");
             }
@@ -273,53 +529,136 @@ impl<R: Read> Lexer<R> {
 
             match next_byte {
                 '0'..='9' => buffer.push(next_byte),
-                '.' | 'E' | 'e' | '+' | '-' => {
+                '.' | 'E' | 'e' => {
                     buffer.push(next_byte);
 
                     is_float = true;
                 }
-                'b' if !is_float && !self.peek_byte_char().is_ascii_digit() => is_byte = true,
+                // Signs are only part of the number right after the exponent
+                '+' | '-' if buffer.ends_with(['E', 'e']) => buffer.push(next_byte),
+                'b' if !is_float => {
+                    let _ = self.next_byte_char();
+                    is_byte = true;
+
+                    break;
+                }
                 _ => break,
             }
 
-            self.next_byte_char();
+            let _ = self.next_byte_char();
         }
 
         if is_float {
-            let float_value = buffer.parse::<f64>().unwrap_or_else(|error| {
-                panic!(
-                    "(lexer) failed to parse value {:?} to float. {}",
-                    buffer, error
-                )
-            });
+            return buffer
+                .parse::<f64>()
+                .map(|value| Token::Float { value })
+                .map_err(|_| {
+                    self.error(LexicalErrorType::MalformedNumber {
+                        number: buffer.clone(),
+                    })
+                });
+        }
+
+        let to_lexical_error = |error: ParseIntError| {
+            let number = if is_byte {
+                format!("{buffer}b")
+            } else {
+                buffer.clone()
+            };
+
+            self.error(match error.kind() {
+                IntErrorKind::PosOverflow => LexicalErrorType::IntegerOverflow { number },
+                _ => LexicalErrorType::MalformedNumber { number },
+            })
+        };
+
+        if is_byte {
+            return buffer
+                .parse::<u8>()
+                .map(|value| Token::Byte { value })
+                .map_err(to_lexical_error);
+        }
+
+        buffer
+            .parse::<i32>()
+            .map(|value| Token::Int { value })
+            .map_err(to_lexical_error)
+    }
+
+    /// Lex a Hex/Octal/Binary number without a decimal point.
+    /// LINK - https://github.com/gleam-lang/gleam/blob/main/compiler-core/src/parse/lexer.rs#L554
+    ///
+    /// Digits may be separated by `_`, which must be followed by a digit, and a
+    /// trailing `b` turns the literal into a byte (`0o17b`). Since `b` is also a
+    /// hex digit, hex bytes need a separator before the suffix (`0xFF_b`). A
+    /// lowercase `b` right after a hex digit could be either, so `0xFFb` is
+    /// malformed, while an uppercase one is always a digit (`0xAB`).
+    ///
+    /// Int literals are read as 32 bits unsigned and stored with the same bit
+    /// pattern, so masks like `0xFFFFFFFF` are valid (and equal to `-1`).
+    fn lex_number_radix(&mut self, radix: u32, prefix: &str) -> LexResult {
+        let mut buffer = String::new();
+        // Whether the last digit was right after a `_`
+        let mut separated = false;
+        let mut last_separated = false;
 
-            return Ok(Token::Float { value: float_value });
+        loop {
+            let next_byte = self.peek_byte_char();
+
+            match next_byte {
+                b'_' => separated = true,
+                byte_char if byte_char.is_ascii_alphanumeric() => {
+                    buffer.push(byte_char as char);
+                    last_separated = separated;
+                    separated = false;
+                }
+                _ => break,
+            }
+
+            let _ = self.next_byte_char();
         }
 
+        // `0b` alone is zero with the byte suffix, not an empty binary literal
+        if radix == 2 && buffer.is_empty() {
+            return Ok(Token::Byte { value: 0 });
+        }
+
+        let is_byte = buffer.ends_with('b') && (radix != 16 || last_separated);
         if is_byte {
-            let byte_value = buffer.parse::<u8>().unwrap_or_else(|error| {
-                panic!(
-                    "(lexer) failed to parse value {:?} to byte. {}",
-                    buffer, error
-                )
-            });
+            buffer.pop();
+        }
 
-            return Ok(Token::Byte { value: byte_value });
+        let suffix = match (is_byte, radix) {
+            (false, _) => "",
+            (true, 16) => "_b",
+            (true, _) => "b",
+        };
+        let trailing_separator = if separated { "_" } else { "" };
+        let number = format!("{prefix}{buffer}{suffix}{trailing_separator}");
+
+        let ambiguous_byte = radix == 16 && !is_byte && buffer.ends_with('b');
+        if separated || ambiguous_byte {
+            return Err(self.error(LexicalErrorType::MalformedNumber { number }));
         }
 
-        let int_value = buffer.parse::<i32>().unwrap_or_else(|error| {
-            panic!(
-                "(lexer) failed to parse value {:?} to int. {}",
-                buffer, error
-            )
-        });
+        let to_lexical_error = |error: ParseIntError| {
+            self.error(match error.kind() {
+                IntErrorKind::PosOverflow => LexicalErrorType::IntegerOverflow { number },
+                _ => LexicalErrorType::MalformedNumber { number },
+            })
+        };
 
-        Ok(Token::Int { value: int_value })
-    }
+        if is_byte {
+            return u8::from_str_radix(&buffer, radix)
+                .map(|value| Token::Byte { value })
+                .map_err(to_lexical_error);
+        }
 
-    // Lex a Hex/Octal/Binary number without a decimal point.
-    fn lex_number_radix(&mut self, _radix: u32, _prefix: &str) -> LexResult {
-        todo!() // TODO - Implement number radix ref: https://github.com/gleam-lang/gleam/blob/main/compiler-core/src/parse/lexer.rs#L554
+        u32::from_str_radix(&buffer, radix)
+            .map(|value| Token::Int {
+                value: value as i32,
+            })
+            .map_err(to_lexical_error)
     }
 
     fn lex_number_or_dots(&mut self) -> LexResult {
@@ -338,44 +677,139 @@ impl<R: Read> Lexer<R> {
         self.lex_number(b'.')
     }
 
-    fn lex_dash_symbol(&mut self) -> LexResult {
-        let next_byte = self.next_byte_char();
-        if next_byte.is_none() {
-            return self.advance();
-        }
+    /// Skips a comment, whose `--` was already read
+    fn skip_comment(&mut self) -> Result<(), LexicalError> {
+        // Block comment, like `--[[ ... ]]`. Otherwise `--[` starts a line comment
+        if self.peek_byte_char() == b'[' {
+            let _ = self.next_byte_char();
 
-        if next_byte.unwrap() == b'>' {
-            return Ok(Token::Arrow);
-        }
+            if let Some(level) = self.read_long_bracket_level() {
+                self.read_long_bracket(level, LexicalErrorType::UnterminatedComment)?;
 
-        while let Some(byte_char) = self.next_byte_char() {
-            if byte_char == b'\n' {
-                self.current_line += 1;
+                return Ok(());
+            }
+        }
 
+        // NOTE - The newline is left to end the open interpolations
+        while self.peek_byte_char() != b'\n' {
+            if self.next_byte_char()?.is_none() {
                 break;
             }
         }
 
-        self.advance()
+        Ok(())
+    }
+
+    /// A `[`, or the start of a long string like `[[ ... ]]` or `[==[ ... ]==]`
+    fn lex_square_left(&mut self) -> LexResult {
+        if !matches!(self.peek_byte_char(), b'[' | b'=') {
+            return Ok(Token::SqurL);
+        }
+
+        let level = self
+            .read_long_bracket_level()
+            .ok_or_else(|| self.error(LexicalErrorType::InvalidLongBracket))?;
+        let buffer = self.read_long_bracket(level, LexicalErrorType::UnterminatedLongString)?;
+
+        Ok(Token::String {
+            value: self.decode_string(buffer)?,
+        })
     }
 
-    fn lex_identifier_or_name(&mut self, current_byte: u8) -> Token {
+    /// Reads the `=`s and the second `[` of an opening long bracket, whose
+    /// first `[` was already read. Its level is the amount of `=`s.
+    fn read_long_bracket_level(&mut self) -> Option<usize> {
+        let mut level = 0;
+        while self.peek_byte_char() == b'=' {
+            let _ = self.next_byte_char();
+            level += 1;
+        }
+
+        if self.peek_byte_char() != b'[' {
+            return None;
+        }
+
+        let _ = self.next_byte_char();
+
+        Some(level)
+    }
+
+    /// Reads up to the closing bracket of the given level, like `]==]`,
+    /// without processing escapes. A newline right after the opening bracket
+    /// is skipped.
+    fn read_long_bracket(
+        &mut self,
+        level: usize,
+        unterminated: LexicalErrorType,
+    ) -> Result<Vec<u8>, LexicalError> {
+        if self.peek_byte_char() == b'\r' {
+            let _ = self.next_byte_char();
+        }
+        if self.peek_byte_char() == b'\n' {
+            let _ = self.next_byte_char();
+        }
+
+        let mut buffer = Vec::new();
+
+        loop {
+            match self.next_byte_char()? {
+                Some(b']') => {
+                    let mut equals = 0;
+                    while equals < level && self.peek_byte_char() == b'=' {
+                        let _ = self.next_byte_char();
+                        equals += 1;
+                    }
+
+                    if equals == level && self.peek_byte_char() == b']' {
+                        let _ = self.next_byte_char();
+
+                        return Ok(buffer);
+                    }
+
+                    // Not the closing bracket, so it's part of the text
+                    buffer.push(b']');
+                    buffer.resize(buffer.len() + equals, b'=');
+                }
+                Some(byte_char) => buffer.push(byte_char),
+                None => return Err(self.error(unterminated)),
+            }
+        }
+    }
+
+    fn lex_identifier_or_name(&mut self, first_character: char) -> LexResult {
         let mut name = String::new();
-        name.push(current_byte as char);
+        name.push(first_character);
 
         loop {
-            let character = self.peek_byte_char() as char;
+            let byte_char = self.peek_byte_char();
+
+            if byte_char.is_ascii() {
+                if !(byte_char.is_ascii_alphanumeric() || byte_char == b'_') {
+                    break;
+                }
 
-            if !(character.is_alphanumeric() || character == '_') {
+                name.push(byte_char as char);
+                let _ = self.next_byte_char();
+
+                continue;
+            }
+
+            // Without Unicode identifiers, the character is reported as the next token
+            if !cfg!(feature = "unicode_identifiers") {
                 break;
             }
 
-            name.push(character);
             let _ = self.next_byte_char();
+            let character = self.read_utf8_char(byte_char)?;
+            if !is_identifier_continue(character) {
+                return Err(self.error(LexicalErrorType::UnexpectedToken { token: character }));
+            }
+
+            name.push(character);
         }
 
         // TODO - optimize by hash
-        match &name as &str {
+        let token = match &name as &str {
             "mut" => Token::Mut,
             "require" => Token::Require,
             "and" => Token::And,
@@ -398,8 +832,11 @@ impl<R: Read> Lexer<R> {
             "false" => Token::Bool { value: false },
             "until" => Token::Until,
             "while" => Token::While,
+            "print" => Token::Print,
             _ => Token::Identifier(name),
-        }
+        };
+
+        Ok(token)
     }
 
     fn check_ahead(
@@ -433,19 +870,29 @@ impl<R: Read> Lexer<R> {
 
         Ok(short_option)
     }
+}
 
-    fn check_complex_ahead(
-        &mut self,
-        ahead_chars: Vec<u8>,
-        short_option: Token,
-        complex_token_reader: fn(&mut Lexer<R>) -> LexResult,
-    ) -> LexResult {
-        for ahead_char in ahead_chars {
-            if self.peek_byte_char() == ahead_char {
-                return complex_token_reader(self);
-            }
-        }
+/// Bytes after the first one of a UTF-8 encoded character
+fn is_utf8_continuation(byte_char: u8) -> bool {
+    (0x80..=0xbf).contains(&byte_char)
+}
 
-        Ok(short_option)
-    }
+#[cfg(feature = "unicode_identifiers")]
+fn is_identifier_start(character: char) -> bool {
+    character == '_' || unicode_ident::is_xid_start(character)
+}
+
+#[cfg(feature = "unicode_identifiers")]
+fn is_identifier_continue(character: char) -> bool {
+    unicode_ident::is_xid_continue(character)
+}
+
+#[cfg(not(feature = "unicode_identifiers"))]
+fn is_identifier_start(character: char) -> bool {
+    character == '_' || character.is_ascii_alphabetic()
+}
+
+#[cfg(not(feature = "unicode_identifiers"))]
+fn is_identifier_continue(character: char) -> bool {
+    character == '_' || character.is_ascii_alphanumeric()
 }
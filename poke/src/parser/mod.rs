@@ -1,4 +1,5 @@
 pub mod errors;
 pub mod lexer;
+#[allow(clippy::module_inception)]
 pub mod parser;
 pub mod tokens;
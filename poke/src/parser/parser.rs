@@ -9,16 +9,17 @@ use self::rules::{ParseRule, Precedence};
 use super::{
     errors::{ParseError, ParseErrorType},
     lexer::Lexer,
-    tokens::Token,
+    tokens::{Span, Spanned, Token},
 };
 use crate::{
     chunk::{ByteCode, Chunk},
     debug,
-    value::ValueType,
+    value::{DataType, Obj, ObjFunction, ObjString, Param, ReturnType, ValueType},
 };
-use std::io::Read;
+use std::{collections::HashMap, io::Read, mem, rc::Rc};
 
-pub type ParseResult = Result<Token, ParseError>;
+/// Every error found in the program, in the order they were found
+pub type LoadResult = Result<(), Vec<ParseError>>;
 
 /// Represents a parsing function used by the parser.
 /// Takes a mutable reference to the parser
@@ -27,18 +28,145 @@ pub type ParseResult = Result<Token, ParseError>;
 /// NOTE - Consider the usage of `&'a dyn Fn(&'a mut Parser<'_, R>)` if more flexibility is needed
 pub type ParseFn<'a, R> = fn(&'a mut Parser<'_, R>) -> Result<(), ParseError>;
 
+/// Locals are addressed by a single byte stack slot
+const LOCALS_MAX: usize = u8::MAX as usize + 1;
+/// The amount of arguments is a single byte operand
+const ARGUMENTS_MAX: usize = u8::MAX as usize;
+/// The amount of upvalues of a closure is a single byte operand
+const UPVALUES_MAX: usize = u8::MAX as usize;
+
+/// What the parser knows about a declared variable
+#[derive(Debug, Clone, Default)]
+struct Variable {
+    /// Unknown for functions and loop variables, whose type depends on what
+    /// they iterate over. They're immutable, so it's never checked.
+    data_type: Option<DataType>,
+    mutable: bool,
+    /// Parameters of a declared function, so calls know which arguments are
+    /// passed by reference
+    signature: Option<Rc<[Param]>>,
+}
+
+/// A variable declared inside a block, living in a stack slot
+/// LINK - https://craftinginterpreters.com/local-variables.html
+#[derive(Debug)]
+struct Local {
+    name: String,
+    /// Scope depth it was declared in
+    depth: usize,
+    variable: Variable,
+    /// Whether a closure captures it, so it's moved into an upvalue when its scope ends
+    is_captured: bool,
+}
+
+/// A variable of an enclosing function used by the one being compiled
+/// LINK - https://craftinginterpreters.com/closures.html#upvalues
+#[derive(Debug)]
+struct Upvalue {
+    /// Stack slot of the enclosing function's local, or index of its upvalue
+    index: u8,
+    is_local: bool,
+}
+
+/// A loop being compiled, which `break` jumps out of
+#[derive(Debug)]
+struct Loop {
+    /// Scope depth around the loop, the locals deeper than it are discarded on `break`
+    scope_depth: usize,
+    /// Jumps to be patched once the end of the loop is known
+    break_jumps: Vec<usize>,
+}
+
+/// What the code being compiled belongs to
+#[derive(Debug, Clone, Copy)]
+enum FunctionKind {
+    Script,
+    Function { return_type: Option<ReturnType> },
+}
+
+/// State of a function whose compilation was paused to compile a nested one
+struct EnclosingFunction {
+    chunk: Chunk,
+    locals: Vec<Local>,
+    upvalues: Vec<Upvalue>,
+    scope_depth: usize,
+    loops: Vec<Loop>,
+    kind: FunctionKind,
+}
+
+/// A global used inside a function before being declared. It's looked up when
+/// the program runs, and checked against its declaration once the whole
+/// program is compiled.
+#[derive(Debug)]
+struct ForwardGlobal {
+    name: String,
+    span: Span,
+    usage: GlobalUsage,
+}
+
+/// How a forward referenced global is used
+#[derive(Debug, Clone, Copy)]
+enum GlobalUsage {
+    Read,
+    Assignment,
+    /// Argument of a `mut` parameter of the given type
+    Reference(DataType),
+    /// Called, its arguments being passed by value
+    Call,
+}
+
+/// Where a variable is stored when the program runs
+#[derive(Debug, Clone, Copy)]
+enum VariableLocation {
+    Global,
+    /// Index of the stack slot
+    Local(u8),
+    /// Index in the upvalues of the closure
+    Upvalue(u8),
+}
+
 /// # Singe-Pass Compilation
 /// LINK - https://craftinginterpreters.com/compiling-expressions.html#single-pass-compilation
 ///
 /// REVIEW - maybe we should just generete the chunk here instead of borrowing
-///
-/// TODO - Write line data
 pub struct Parser<'a, R: Read> {
+    /// Chunk of the function being compiled, the script's one being borrowed
     pub chunk: &'a mut Chunk,
 
     lex: Option<Lexer<R>>,
     previus_token: Token,
     current_token: Token,
+    previus_span: Span,
+    current_span: Span,
+
+    /// Global variables declared so far, resolved at compile time so
+    /// assignments can be checked before the program runs
+    globals: HashMap<String, Variable>,
+    /// Locals in scope, in the same order as their stack slots
+    locals: Vec<Local>,
+    /// Variables of the enclosing functions captured by the one being compiled
+    upvalues: Vec<Upvalue>,
+    /// Amount of blocks surrounding the current code, `0` being the global scope
+    scope_depth: usize,
+    /// Loops surrounding the current code, the innermost being the last
+    loops: Vec<Loop>,
+    function_kind: FunctionKind,
+    /// Functions surrounding the one being compiled, the innermost being the last
+    enclosing: Vec<EnclosingFunction>,
+    /// Parameters of the function the expression compiled so far evaluates
+    /// to, when it's a variable holding a declared function
+    callee_signature: Option<Rc<[Param]>>,
+    /// Whether the expression being compiled is a statement on its own, so an
+    /// index ending it may be assigned to, like `t[k] = v`
+    can_assign: bool,
+    /// Globals used by functions before being declared, like mutually
+    /// recursive functions calling each other
+    forward_globals: Vec<ForwardGlobal>,
+
+    errors: Vec<ParseError>,
+    /// Set after an error, suppressing the errors it causes until the parser
+    /// synchronizes with the start of the next statement
+    panic_mode: bool,
 }
 
 impl<'a, R: Read> Parser<'a, R> {
@@ -48,180 +176,2599 @@ impl<'a, R: Read> Parser<'a, R> {
             lex: None,
             previus_token: Token::EoS,
             current_token: Token::EoS,
+            previus_span: Span::default(),
+            current_span: Span::default(),
+            globals: HashMap::new(),
+            locals: Vec::new(),
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            loops: Vec::new(),
+            function_kind: FunctionKind::Script,
+            enclosing: Vec::new(),
+            callee_signature: None,
+            can_assign: false,
+            forward_globals: Vec::new(),
+            errors: Vec::new(),
+            panic_mode: false,
         }
     }
 
     /// Run the parser (for now)
-    pub fn load(&mut self, input: R) -> ParseResult {
+    ///
+    /// Parsing doesn't stop at the first error, every error found is returned.
+    /// If there's any, the chunk is left empty so it can't be executed.
+    pub fn load(&mut self, input: R) -> LoadResult {
         self.lex = Some(Lexer::new(input));
 
         // #[cfg(feature = "debug_trace_lex_execution")]
         // _disassemble_lexer(&mut lexer, "operators");
 
-        // NOTE - If error found: stop compiling and then propagate error
-        self.advance().map_err(|err| {
-            self.finish_code_execution(0);
+        self.advance();
 
-            #[cfg(feature = "debug_trace_execution")]
-            debug::_disassemble_chunk(self.chunk, "parser test");
+        while self.current_token != Token::EoS {
+            self.parse_declaration();
+        }
 
-            err
-        })?;
+        self.check_forward_globals();
 
-        self.parse_expression()?;
-        self.consume(Token::EoS)?;
+        if !self.errors.is_empty() {
+            *self.chunk = Chunk::new();
 
-        self.finish_code_execution(0);
+            return Err(mem::take(&mut self.errors));
+        }
+
+        self.finish_code_execution();
 
         #[cfg(feature = "debug_trace_execution")]
         debug::_disassemble_chunk(self.chunk, "parser test");
 
-        Ok(Token::Nil)
+        Ok(())
     }
 
-    fn finish_code_execution(&mut self, line: i32) {
-        self.chunk.write_chunk(ByteCode::Return as u8, line)
+    /// Reports the forward referenced globals never declared, or declared in a
+    /// way that doesn't allow how they were used
+    fn check_forward_globals(&mut self) {
+        for ForwardGlobal { name, span, usage } in mem::take(&mut self.forward_globals) {
+            let error = match (self.globals.get(&name), usage) {
+                (None, _) => ParseErrorType::UndeclaredVariable { name },
+                (Some(variable), GlobalUsage::Assignment) if !variable.mutable => {
+                    ParseErrorType::ImmutableAssignment { name }
+                }
+                (Some(variable), GlobalUsage::Reference(_)) if !variable.mutable => {
+                    ParseErrorType::ImmutableArgument { name }
+                }
+                (Some(variable), GlobalUsage::Call) => match &variable.signature {
+                    Some(params) if params.iter().any(|param| param.mutable) => {
+                        ParseErrorType::ForwardMutableCall { name }
+                    }
+                    _ => continue,
+                },
+                (Some(variable), GlobalUsage::Reference(expected)) => match variable.data_type {
+                    Some(found) if found != expected => ParseErrorType::MismatchedReference {
+                        name,
+                        expected,
+                        found,
+                    },
+                    _ => continue,
+                },
+                _ => continue,
+            };
+
+            self.errors.push(ParseError::new(error, span));
+        }
+
+        // NOTE - Keeps the errors in the order they appear in the source
+        self.errors.sort_by_key(|error| error.span.start);
     }
 
-    fn advance(&mut self) -> Result<(), ParseError> {
-        self.previus_token = self.current_token.clone();
-        self.current_token = self.advance_lex()?;
+    fn finish_code_execution(&mut self) {
+        self.chunk
+            .write_chunk(ByteCode::Return as u8, self.previus_span)
+    }
 
-        Ok(())
+    /// Records an error, unless it was caused by a previous one
+    fn report(&mut self, error: ParseError) {
+        if self.panic_mode {
+            return;
+        }
+
+        self.panic_mode = true;
+        self.errors.push(error);
+    }
+
+    /// Panic mode recovery: skips tokens until a statement boundary, so the
+    /// errors reported after it are not caused by the previous one.
+    /// LINK - https://craftinginterpreters.com/parsing-expressions.html#synchronizing-a-recursive-descent-parser
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+
+        while self.current_token != Token::EoS {
+            // Statements are separated by `;` or new lines
+            if self.previus_token == Token::SemiColon
+                || self.current_span.line > self.previus_span.line
+            {
+                return;
+            }
+
+            match self.current_token {
+                Token::Function
+                | Token::If
+                | Token::ElseIf
+                | Token::Else
+                | Token::While
+                | Token::For
+                | Token::Repeat
+                | Token::Until
+                | Token::Do
+                | Token::End
+                | Token::Mut
+                | Token::Return
+                | Token::Break
+                | Token::Require => return,
+
+                _ => self.advance(),
+            }
+        }
+    }
+
+    /// Moves to the next token. Lexical errors are reported and skipped, so the
+    /// parser can keep going.
+    fn advance(&mut self) {
+        loop {
+            match self.advance_lex() {
+                Ok((token, span)) => {
+                    self.previus_token = mem::replace(&mut self.current_token, token);
+                    self.previus_span = mem::replace(&mut self.current_span, span);
+
+                    return;
+                }
+                Err(error) => self.report(error),
+            }
+        }
     }
 
     fn consume(&mut self, expected_token: Token) -> Result<(), ParseError> {
         if self.current_token == expected_token {
-            return self.advance();
+            self.advance();
+
+            return Ok(());
         }
 
-        Err(ParseError {
-            error: ParseErrorType::UnexpectedToken {
-                token: self.current_token.clone(),
+        Err(ParseError::new(
+            ParseErrorType::ExpectedToken {
+                expected: expected_token,
+                found: self.current_token.clone(),
             },
-            line: 0,
-        })
+            self.current_span,
+        ))
+    }
+
+    /// Consumes the current token if it's the given one
+    fn match_token(&mut self, token: Token) -> bool {
+        if self.current_token != token {
+            return false;
+        }
+
+        self.advance();
+
+        true
     }
 
     // REVIEW - I don't know if borrowing as mutable every iteration is a good ideia
-    fn _peek_into_lex(&mut self) -> Result<&Token, ParseError> {
+    fn peek_into_lex(&mut self) -> Result<&Spanned, ParseError> {
         self.lex
             .as_mut()
             .expect("lex should not be used before loaded")
             .peek()
-            .map_err(|error| ParseError {
-                error: ParseErrorType::LexError { error },
-                line: 0,
-            })
+            .map_err(ParseError::from)
     }
 
     // REVIEW - I don't know if borrowing as mutable every iteration is a good ideia
-    fn advance_lex(&mut self) -> Result<Token, ParseError> {
+    fn advance_lex(&mut self) -> Result<Spanned, ParseError> {
         self.lex
             .as_mut()
             .expect("lex should not be used before loaded")
             .advance()
-            .map_err(|error| ParseError {
-                error: ParseErrorType::LexError { error },
-                line: 0,
-            })
+            .map_err(ParseError::from)
     }
 
-    fn parse_precedence(&mut self, precedence: u8) -> Result<(), ParseError> {
-        self.advance()?;
-        let previous_tok_rule: &ParseRule<'_, R> = rules::get_rule(&self.previus_token);
-        let prefix = previous_tok_rule.prefix;
+    /// Compiles a statement, recovering from any error found in it
+    fn parse_declaration(&mut self) {
+        if let Err(error) = self.parse_statement() {
+            self.report(error);
+        }
 
-        println!("RUN PREFIX FROM: {:?}", self.previus_token);
-        prefix.map_or_else(
-            || Err(ParseError::new(ParseErrorType::ExpectedExpression, 156)),
-            |prefix_fn| prefix_fn(self),
-        )?;
+        // NOTE - Keep looking for errors in the rest of the program
+        if self.panic_mode {
+            self.synchronize();
+        }
+    }
+
+    fn parse_statement(&mut self) -> Result<(), ParseError> {
+        if self.match_token(Token::Print) {
+            return self.parse_print_statement();
+        }
 
-        let mut current_tok_rule: &ParseRule<'_, R> = rules::get_rule(&self.current_token);
-        while precedence <= current_tok_rule.precedence as u8 {
-            self.advance()?;
+        if self.match_token(Token::Mut) {
+            return self.parse_variable_declaration(true);
+        }
 
-            current_tok_rule = rules::get_rule(&self.current_token);
+        if self.is_declaration() {
+            return self.parse_variable_declaration(false);
+        }
 
-            println!("{:?}", self.previus_token);
-            println!(
-                "precende {:} <= ({:?}) Precendece of: {:?}",
-                precedence, self.previus_token, current_tok_rule.precedence as u8
-            );
+        if self.is_assignment()? {
+            return self.parse_assignment();
+        }
 
-            println!("RUN INFIX FROM: {:?}", self.previus_token);
-            let infix = rules::get_rule(&self.previus_token).infix;
-            if let Some(infix_fn) = infix {
-                infix_fn(self)?;
+        if self.is_command_call()? {
+            return self.parse_command_call();
+        }
+
+        if self.match_token(Token::If) {
+            return self.parse_if_statement();
+        }
+
+        if self.match_token(Token::While) {
+            return self.parse_while_statement();
+        }
+
+        if self.match_token(Token::Function) {
+            return self.parse_function_declaration();
+        }
+
+        if self.match_token(Token::Return) {
+            return self.parse_return_statement();
+        }
+
+        if self.match_token(Token::For) {
+            return self.parse_for_statement();
+        }
+
+        if self.match_token(Token::Repeat) {
+            return self.parse_repeat_statement();
+        }
+
+        if self.match_token(Token::Break) {
+            return self.parse_break_statement();
+        }
+
+        if self.match_token(Token::Do) {
+            self.parse_scoped_block();
+
+            return self.consume(Token::End);
+        }
+
+        // Empty statement
+        if self.match_token(Token::SemiColon) {
+            return Ok(());
+        }
+
+        // Blocks stop before their closing token, so it can only get here
+        // when there's no block to close
+        if self.is_block_end() {
+            self.advance();
+
+            return Err(ParseError::new(
+                ParseErrorType::UnexpectedToken {
+                    token: self.previus_token.clone(),
+                },
+                self.previus_span,
+            ));
+        }
+
+        self.parse_expression_statement()
+    }
+
+    /// Whether the current token can only start a statement, like a keyword
+    /// or a type name
+    fn starts_statement(&self) -> bool {
+        self.is_declaration()
+            || matches!(
+                self.current_token,
+                Token::Print
+                    | Token::Mut
+                    | Token::If
+                    | Token::While
+                    | Token::Function
+                    | Token::Return
+                    | Token::For
+                    | Token::Repeat
+                    | Token::Break
+                    | Token::Do
+            )
+    }
+
+    /// Whether a declaration starts at the current token, type names can't
+    /// be used as variable names so they always start one
+    fn is_declaration(&self) -> bool {
+        matches!(&self.current_token, Token::Identifier(name) if DataType::from_name(name).is_some())
+    }
+
+    /// Whether an assignment starts at the current token: `<name> =`
+    fn is_assignment(&mut self) -> Result<bool, ParseError> {
+        if !matches!(self.current_token, Token::Identifier(_)) {
+            return Ok(false);
+        }
+
+        Ok(self.peek_into_lex()?.0 == Token::Assign)
+    }
+
+    /// Whether a call without parentheses starts at the current token:
+    /// `<name> <argument>`, the argument being on the same line
+    fn is_command_call(&mut self) -> Result<bool, ParseError> {
+        if !matches!(self.current_token, Token::Identifier(_)) {
+            return Ok(false);
+        }
+
+        let line = self.current_span.line;
+        let (token, span) = self.peek_into_lex()?;
+
+        Ok(span.line == line
+            && matches!(
+                token,
+                Token::Identifier(_)
+                    | Token::Int { .. }
+                    | Token::Float { .. }
+                    | Token::Byte { .. }
+                    | Token::String { .. }
+                    | Token::Interpolation { .. }
+                    | Token::Bool { .. }
+                    | Token::Nil
+            ))
+    }
+
+    /// Whether the current token closes a block
+    fn is_block_end(&self) -> bool {
+        matches!(
+            self.current_token,
+            Token::End | Token::Else | Token::ElseIf | Token::Until | Token::EoS
+        )
+    }
+
+    fn begin_scope(&mut self) {
+        self.scope_depth += 1;
+    }
+
+    /// Discards the locals declared in the scope being closed
+    fn end_scope(&mut self) {
+        self.scope_depth -= 1;
+
+        self.emit_locals_pop(self.scope_depth, self.current_span);
+        self.locals.truncate(self.locals_in_scope(self.scope_depth));
+    }
+
+    /// Amount of locals declared up to the given scope depth
+    fn locals_in_scope(&self, depth: usize) -> usize {
+        self.locals
+            .iter()
+            .rposition(|local| local.depth <= depth)
+            .map_or(0, |index| index + 1)
+    }
+
+    /// Writes the instructions discarding the locals deeper than the given
+    /// scope depth, without forgetting them. The captured ones are closed.
+    fn emit_locals_pop(&mut self, depth: usize, span: Span) {
+        let mut count = 0;
+
+        for slot in (self.locals_in_scope(depth)..self.locals.len()).rev() {
+            if !self.locals[slot].is_captured {
+                count += 1;
+
+                continue;
+            }
+
+            self.emit_pops(count, span);
+            self.chunk.write_chunk(ByteCode::CloseUpvalue as u8, span);
+            count = 0;
+        }
+
+        self.emit_pops(count, span);
+    }
+
+    fn emit_pops(&mut self, mut count: usize, span: Span) {
+        while count > 0 {
+            if count == 1 {
+                self.chunk.write_chunk(ByteCode::Pop as u8, span);
+
+                return;
             }
+
+            let popped = count.min(u8::MAX as usize);
+            self.chunk.write_chunk(ByteCode::PopN as u8, span);
+            self.chunk.write_chunk(popped as u8, span);
+
+            count -= popped;
+        }
+    }
+
+    /// Compiles statements until the end of the block, without consuming it
+    fn parse_block(&mut self) {
+        while !self.is_block_end() {
+            self.parse_declaration();
+        }
+    }
+
+    /// Compiles a block whose locals are discarded when it ends
+    fn parse_scoped_block(&mut self) {
+        self.begin_scope();
+        self.parse_block();
+        self.end_scope();
+    }
+
+    /// `if <condition> then <block> {elseif <condition> then <block>} [else <block>] end`
+    ///
+    /// Every branch pops the condition and jumps to the end of the statement
+    /// once it's done, while a false condition jumps to the next branch.
+    /// LINK - https://craftinginterpreters.com/jumping-back-and-forth.html#if-statements
+    fn parse_if_statement(&mut self) -> Result<(), ParseError> {
+        let mut exit_jumps = Vec::new();
+
+        loop {
+            let condition_span = self.current_span;
+            self.parse_expression()?;
+            self.consume(Token::Then)?;
+
+            let else_jump = self.emit_jump(ByteCode::JumpIfFalse, condition_span);
+            self.chunk.write_chunk(ByteCode::Pop as u8, condition_span);
+            self.parse_scoped_block();
+
+            exit_jumps.push(self.emit_jump(ByteCode::Jump, self.current_span));
+            self.patch_jump(else_jump);
+            self.chunk.write_chunk(ByteCode::Pop as u8, condition_span);
+
+            if !self.match_token(Token::ElseIf) {
+                break;
+            }
+        }
+
+        if self.match_token(Token::Else) {
+            self.parse_scoped_block();
+        }
+
+        self.consume(Token::End)?;
+
+        for exit_jump in exit_jumps {
+            self.patch_jump(exit_jump);
         }
 
         Ok(())
     }
 
-    fn parse_expression(&mut self) -> Result<(), ParseError> {
-        self.parse_precedence(Precedence::Assignment as u8)
+    /// `[mut] <type> <name> = <expression>`, the `mut` being already consumed
+    ///
+    /// Declarations inside blocks are locals, which may shadow the variables
+    /// of outer scopes but not the ones of their own scope.
+    fn parse_variable_declaration(&mut self, mutable: bool) -> Result<(), ParseError> {
+        let data_type = self.parse_type()?;
+        let (name, name_span) = self.parse_identifier()?;
+        let variable = Variable {
+            data_type: Some(data_type),
+            mutable,
+            ..Default::default()
+        };
+
+        self.check_redeclaration(&name, name_span)?;
+
+        self.consume(Token::Assign)?;
+        self.parse_typed_expression(data_type, false)?;
+
+        // NOTE - Declared after its initializer, so it can't refer to itself
+        if self.scope_depth > 0 {
+            // The initializer's value is left in the stack, becoming the local's slot
+            return self.add_local(name, variable, name_span);
+        }
+
+        self.chunk
+            .write_identifier(ByteCode::DefineGlobal, &name, name_span);
+        self.globals.insert(name, variable);
+
+        Ok(())
     }
 
-    fn _parse_prefix_expression(&mut self) -> Result<(), ParseError> {
-        todo!()
+    /// Variables can't be declared twice in the same scope, but may shadow the
+    /// ones of outer scopes
+    fn check_redeclaration(&self, name: &str, span: Span) -> Result<(), ParseError> {
+        let already_declared = match self.scope_depth {
+            0 => self.globals.contains_key(name),
+            _ => self
+                .locals
+                .iter()
+                .rev()
+                .take_while(|local| local.depth == self.scope_depth)
+                .any(|local| local.name == name),
+        };
+
+        if already_declared {
+            return Err(ParseError::new(
+                ParseErrorType::AlreadyDeclared {
+                    name: name.to_string(),
+                },
+                span,
+            ));
+        }
+
+        Ok(())
     }
 
-    fn parse_grouping(&mut self) -> Result<(), ParseError> {
-        println!("Started grouping at: {:?}", self.current_token);
-        self.parse_expression()?;
-        self.consume(Token::ParR)?;
+    /// `function <name>(<params>) [-> <type>] <block> end`
+    fn parse_function_declaration(&mut self) -> Result<(), ParseError> {
+        let (name, name_span) = self.parse_identifier()?;
+        let variable = Variable::default();
+
+        // NOTE - Declared before its body, so it can call itself
+        self.check_redeclaration(&name, name_span)?;
+        match self.scope_depth {
+            0 => {
+                self.globals.insert(name.clone(), variable);
+            }
+            _ => self.add_local(name.clone(), variable, name_span)?,
+        }
+
+        let (function, upvalues) = self.parse_function(&name)?;
+        self.chunk.write_constant(
+            ValueType::Object(Obj::Function(Rc::new(function))),
+            name_span,
+        );
+        self.emit_closure(&upvalues, name_span);
+
+        // Locals take the slot the function is pushed to
+        if self.scope_depth == 0 {
+            self.chunk
+                .write_identifier(ByteCode::DefineGlobal, &name, name_span);
+        }
 
         Ok(())
     }
 
-    fn parse_unary_op(&mut self) -> Result<(), ParseError> {
-        let sufix_operator = self.previus_token.clone();
+    /// Compiles the parameters and the body of a function into its own chunk,
+    /// returning the variables it captures
+    fn parse_function(&mut self, name: &str) -> Result<(ObjFunction, Vec<Upvalue>), ParseError> {
+        let mut function = ObjFunction::new(name);
 
-        // Compile operand
-        self.parse_precedence(Precedence::Unary as u8)?;
+        self.begin_function();
+        let result = self.parse_function_body(&mut function);
+        let (chunk, upvalues) = self.end_function();
+        function.chunk = chunk;
+        result?;
 
-        match sufix_operator {
-            Token::Sub => {
-                self.chunk.write_chunk(ByteCode::Negate as u8, 0);
+        #[cfg(feature = "debug_trace_execution")]
+        debug::_disassemble_chunk(&function.chunk, name);
 
-                Ok(())
+        Ok((function, upvalues))
+    }
+
+    /// Turns the function on top of the stack into a closure of the given upvalues
+    fn emit_closure(&mut self, upvalues: &[Upvalue], span: Span) {
+        self.chunk.write_chunk(ByteCode::Closure as u8, span);
+        self.chunk.write_chunk(upvalues.len() as u8, span);
+
+        for upvalue in upvalues {
+            self.chunk.write_chunk(upvalue.is_local as u8, span);
+            self.chunk.write_chunk(upvalue.index, span);
+        }
+    }
+
+    fn parse_function_body(&mut self, function: &mut ObjFunction) -> Result<(), ParseError> {
+        self.consume(Token::ParL)?;
+
+        if self.current_token != Token::ParR {
+            loop {
+                let param = self.parse_param()?;
+
+                if function.params.len() == ARGUMENTS_MAX {
+                    return Err(ParseError::new(
+                        ParseErrorType::TooManyArguments,
+                        self.previus_span,
+                    ));
+                }
+
+                function.params.push(param);
+
+                if !self.match_token(Token::Comma) {
+                    break;
+                }
             }
-            Token::Len => todo!(),
-            Token::Not => todo!(),
-            Token::BitNot => todo!(),
-            _ => Ok(()), // unreachable
         }
+
+        self.consume(Token::ParR)?;
+        self.declare_signature(&function.name, &function.params);
+
+        if self.match_token(Token::Arrow) {
+            function.return_type = self.parse_return_type()?;
+        }
+
+        self.function_kind = FunctionKind::Function {
+            return_type: function.return_type,
+        };
+
+        self.parse_block();
+
+        // Falling off the end returns `nil`, an error if a value was expected
+        self.chunk
+            .write_chunk(ByteCode::Nil as u8, self.current_span);
+        if let Some(return_type) = function.return_type {
+            self.emit_check_type(
+                return_type.data_type,
+                return_type.nullable,
+                self.current_span,
+            );
+        }
+        self.chunk
+            .write_chunk(ByteCode::Return as u8, self.current_span);
+
+        self.consume(Token::End)
     }
 
-    fn parse_binary_op(&mut self) -> Result<(), ParseError> {
-        let operator = self.previus_token.clone();
-        let rule: &ParseRule<'_, R> = rules::get_rule(&self.previus_token);
+    /// `[mut] <type>[?] <name>`, declared as a local of the function
+    fn parse_param(&mut self) -> Result<Param, ParseError> {
+        let mutable = self.match_token(Token::Mut);
+        let data_type = self.parse_type()?;
+        let nullable = self.match_token(Token::Question);
+        let (name, name_span) = self.parse_identifier()?;
+
+        // NOTE - Recorded without entering panic mode, since the rest of the
+        // function is fine and its errors must still be reported
+        if let Err(error) = self.check_redeclaration(&name, name_span) {
+            self.errors.push(error);
+        }
 
-        self.parse_precedence(rule.precedence as u8 + 1)?;
+        self.add_local(
+            name,
+            Variable {
+                data_type: Some(data_type),
+                mutable,
+                ..Default::default()
+            },
+            name_span,
+        )?;
 
-        match operator {
-            Token::Add => self.chunk.write_chunk(ByteCode::Add as u8, 0),
-            Token::Sub => self.chunk.write_chunk(ByteCode::Subtract as u8, 0),
-            Token::Mul => self.chunk.write_chunk(ByteCode::Multiply as u8, 0),
-            Token::Div => self.chunk.write_chunk(ByteCode::Divide as u8, 0),
+        Ok(Param {
+            data_type,
+            nullable,
+            mutable,
+        })
+    }
 
-            _ => return Ok(()),
+    /// Makes the parameters of the function being compiled known to its
+    /// callers, the function itself included when it's a global
+    fn declare_signature(&mut self, name: &str, params: &[Param]) {
+        let enclosing = self
+            .enclosing
+            .last_mut()
+            .expect("every function has an enclosing one");
+
+        let variable = match enclosing.scope_depth {
+            0 => self.globals.get_mut(name),
+            _ => enclosing
+                .locals
+                .iter_mut()
+                .rev()
+                .find(|local| local.name == name)
+                .map(|local| &mut local.variable),
         };
 
-        Ok(())
+        if let Some(variable) = variable {
+            variable.signature = Some(Rc::from(params));
+        }
     }
 
-    fn parse_number(&mut self) -> Result<(), ParseError> {
-        match self.previus_token {
-            Token::Int { value } => self.chunk.write_constant(ValueType::Int(value), 0),
-            Token::Float { value } => self.chunk.write_constant(ValueType::Float(value), 0),
-            Token::Byte { value } => self.chunk.write_constant(ValueType::Byte(value), 0),
+    /// A type, which may be followed by `?` to allow `nil`, or `void` for
+    /// functions that return nothing
+    fn parse_return_type(&mut self) -> Result<Option<ReturnType>, ParseError> {
+        if matches!(&self.current_token, Token::Identifier(name) if name == "void") {
+            self.advance();
 
-            _ => return Ok(()),
+            return Ok(None);
+        }
+
+        let data_type = self.parse_type()?;
+        let nullable = self.match_token(Token::Question);
+
+        Ok(Some(ReturnType {
+            data_type,
+            nullable,
+        }))
+    }
+
+    /// Starts compiling a function into a new chunk, saving the state of the
+    /// enclosing one
+    /// LINK - https://craftinginterpreters.com/calls-and-functions.html#function-declarations
+    fn begin_function(&mut self) {
+        self.enclosing.push(EnclosingFunction {
+            chunk: mem::replace(self.chunk, Chunk::new()),
+            locals: mem::take(&mut self.locals),
+            upvalues: mem::take(&mut self.upvalues),
+            scope_depth: mem::replace(&mut self.scope_depth, 1),
+            loops: mem::take(&mut self.loops),
+            kind: mem::replace(
+                &mut self.function_kind,
+                FunctionKind::Function { return_type: None },
+            ),
+        });
+
+        // The first slot holds the called function, its name can't be used
+        self.locals.push(Local {
+            name: String::new(),
+            depth: self.scope_depth,
+            variable: Variable::default(),
+            is_captured: false,
+        });
+    }
+
+    /// Goes back to compiling the enclosing function, returning the chunk and
+    /// the upvalues of the finished one
+    fn end_function(&mut self) -> (Chunk, Vec<Upvalue>) {
+        let enclosing = self
+            .enclosing
+            .pop()
+            .expect("every function has an enclosing one");
+
+        self.locals = enclosing.locals;
+        self.scope_depth = enclosing.scope_depth;
+        self.loops = enclosing.loops;
+        self.function_kind = enclosing.kind;
+
+        let upvalues = mem::replace(&mut self.upvalues, enclosing.upvalues);
+
+        (mem::replace(self.chunk, enclosing.chunk), upvalues)
+    }
+
+    /// `return [<expression>]`, the value being on the same line
+    fn parse_return_statement(&mut self) -> Result<(), ParseError> {
+        let return_span = self.previus_span;
+        let has_value = !self.is_block_end()
+            && self.current_token != Token::SemiColon
+            && self.current_span.line == return_span.line;
+
+        match self.function_kind {
+            FunctionKind::Function {
+                return_type: Some(return_type),
+            } if has_value => {
+                self.parse_typed_expression(return_type.data_type, return_type.nullable)?
+            }
+
+            // NOTE - A bare return gives `nil`, which only nullable types allow
+            FunctionKind::Function {
+                return_type:
+                    Some(ReturnType {
+                        data_type,
+                        nullable: false,
+                    }),
+            } => {
+                return Err(ParseError::new(
+                    ParseErrorType::MissingReturnValue {
+                        expected: data_type,
+                    },
+                    return_span,
+                ))
+            }
+
+            // The script has no caller to give a value to
+            FunctionKind::Script if !has_value => {}
+
+            FunctionKind::Function { return_type: None } | FunctionKind::Script if has_value => {
+                return Err(ParseError::new(
+                    ParseErrorType::UnexpectedReturnValue,
+                    self.current_span,
+                ))
+            }
+
+            _ => self.chunk.write_chunk(ByteCode::Nil as u8, return_span),
+        }
+
+        self.chunk.write_chunk(ByteCode::Return as u8, return_span);
+
+        Ok(())
+    }
+
+    /// Declares a local living in the next stack slot
+    fn add_local(
+        &mut self,
+        name: String,
+        variable: Variable,
+        span: Span,
+    ) -> Result<(), ParseError> {
+        if self.locals.len() == LOCALS_MAX {
+            return Err(ParseError::new(ParseErrorType::TooManyLocals, span));
         }
 
+        self.locals.push(Local {
+            name,
+            depth: self.scope_depth,
+            variable,
+            is_captured: false,
+        });
+
         Ok(())
     }
+
+    /// `<name> = <expression>`
+    fn parse_assignment(&mut self) -> Result<(), ParseError> {
+        let (name, name_span) = self.parse_identifier()?;
+        let (variable, location) = self.resolve_variable(&name, name_span)?;
+        self.set_forward_usage(&name, location, GlobalUsage::Assignment);
+
+        if !variable.mutable {
+            return Err(ParseError::new(
+                ParseErrorType::ImmutableAssignment { name },
+                name_span,
+            ));
+        }
+
+        self.consume(Token::Assign)?;
+        match variable.data_type {
+            Some(data_type) => self.parse_typed_expression(data_type, false)?,
+            None => self.parse_expression()?,
+        }
+
+        match location {
+            VariableLocation::Global => {
+                self.chunk
+                    .write_identifier(ByteCode::SetGlobal, &name, name_span)
+            }
+            VariableLocation::Local(slot) => {
+                self.chunk.write_chunk(ByteCode::SetLocal as u8, name_span);
+                self.chunk.write_chunk(slot, name_span);
+            }
+            VariableLocation::Upvalue(index) => {
+                self.chunk
+                    .write_chunk(ByteCode::SetUpvalue as u8, name_span);
+                self.chunk.write_chunk(index, name_span);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compiles an expression whose value is stored as the given type, or is
+    /// `nil` when the type is nullable
+    fn parse_typed_expression(
+        &mut self,
+        data_type: DataType,
+        nullable: bool,
+    ) -> Result<(), ParseError> {
+        let expression_start = self.current_span;
+
+        self.parse_expression()?;
+
+        let expression_span = expression_start.to(self.previus_span);
+        self.emit_check_type(data_type, nullable, expression_span);
+
+        Ok(())
+    }
+
+    /// Checks the value on top of the stack against the type, which also
+    /// accepts `nil` when it's nullable
+    fn emit_check_type(&mut self, data_type: DataType, nullable: bool, span: Span) {
+        let operand = match nullable {
+            true => data_type as u8 | DataType::NULLABLE,
+            false => data_type as u8,
+        };
+
+        self.chunk.write_chunk(ByteCode::CheckType as u8, span);
+        self.chunk.write_chunk(operand, span);
+    }
+
+    fn parse_type(&mut self) -> Result<DataType, ParseError> {
+        if let Token::Identifier(name) = &self.current_token {
+            if let Some(data_type) = DataType::from_name(name) {
+                self.advance();
+
+                return Ok(data_type);
+            }
+        }
+
+        Err(ParseError::new(
+            ParseErrorType::ExpectedType {
+                found: self.current_token.clone(),
+            },
+            self.current_span,
+        ))
+    }
+
+    fn parse_identifier(&mut self) -> Result<(String, Span), ParseError> {
+        match &self.current_token {
+            Token::Identifier(name) if DataType::from_name(name).is_none() => {
+                let name = name.clone();
+                self.advance();
+
+                Ok((name, self.previus_span))
+            }
+
+            _ => Err(ParseError::new(
+                ParseErrorType::ExpectedIdentifier {
+                    found: self.current_token.clone(),
+                },
+                self.current_span,
+            )),
+        }
+    }
+
+    /// Finds the variable with the given name, looking from the innermost scope
+    /// to the global one, through the enclosing functions
+    fn resolve_variable(
+        &mut self,
+        name: &str,
+        span: Span,
+    ) -> Result<(Variable, VariableLocation), ParseError> {
+        if let Some(slot) = self.locals.iter().rposition(|local| local.name == name) {
+            return Ok((
+                self.locals[slot].variable.clone(),
+                VariableLocation::Local(slot as u8),
+            ));
+        }
+
+        if let Some((index, variable)) = self.resolve_upvalue(self.enclosing.len(), name, span)? {
+            return Ok((variable, VariableLocation::Upvalue(index)));
+        }
+
+        match self.globals.get(name) {
+            Some(variable) => Ok((variable.clone(), VariableLocation::Global)),
+            // NOTE - Functions may use globals declared after them, which exist
+            // by the time they're called
+            None if !self.enclosing.is_empty() => {
+                self.forward_globals.push(ForwardGlobal {
+                    name: name.to_string(),
+                    span,
+                    usage: GlobalUsage::Read,
+                });
+
+                let variable = Variable {
+                    mutable: true,
+                    ..Variable::default()
+                };
+                Ok((variable, VariableLocation::Global))
+            }
+            None => Err(ParseError::new(
+                ParseErrorType::UndeclaredVariable {
+                    name: name.to_string(),
+                },
+                span,
+            )),
+        }
+    }
+
+    /// Records how a global just resolved is used, when it's not declared yet
+    fn set_forward_usage(&mut self, name: &str, location: VariableLocation, usage: GlobalUsage) {
+        if !matches!(location, VariableLocation::Global) || self.globals.contains_key(name) {
+            return;
+        }
+
+        if let Some(forward) = self.forward_globals.last_mut() {
+            forward.usage = usage;
+        }
+    }
+
+    /// Finds a local of the functions enclosing the one at the given level of
+    /// nesting, the script being the level `0`. Every function in between
+    /// captures it as an upvalue.
+    /// LINK - https://craftinginterpreters.com/closures.html#flattening-upvalues
+    fn resolve_upvalue(
+        &mut self,
+        level: usize,
+        name: &str,
+        span: Span,
+    ) -> Result<Option<(u8, Variable)>, ParseError> {
+        let Some(enclosing_level) = level.checked_sub(1) else {
+            return Ok(None);
+        };
+
+        let enclosing = &mut self.enclosing[enclosing_level];
+        if let Some(slot) = enclosing
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+        {
+            let local = &mut enclosing.locals[slot];
+            local.is_captured = true;
+
+            let variable = local.variable.clone();
+            return self.add_upvalue(level, slot as u8, true, variable, span);
+        }
+
+        match self.resolve_upvalue(enclosing_level, name, span)? {
+            Some((index, variable)) => self.add_upvalue(level, index, false, variable, span),
+            None => Ok(None),
+        }
+    }
+
+    /// Index of the upvalue in the function at the given level of nesting,
+    /// adding it if it's not captured yet
+    fn add_upvalue(
+        &mut self,
+        level: usize,
+        index: u8,
+        is_local: bool,
+        variable: Variable,
+        span: Span,
+    ) -> Result<Option<(u8, Variable)>, ParseError> {
+        let upvalues = match self.enclosing.get_mut(level) {
+            Some(function) => &mut function.upvalues,
+            None => &mut self.upvalues,
+        };
+
+        if let Some(existing) = upvalues
+            .iter()
+            .position(|upvalue| upvalue.index == index && upvalue.is_local == is_local)
+        {
+            return Ok(Some((existing as u8, variable)));
+        }
+
+        if upvalues.len() == UPVALUES_MAX {
+            return Err(ParseError::new(ParseErrorType::TooManyUpvalues, span));
+        }
+
+        upvalues.push(Upvalue { index, is_local });
+
+        Ok(Some(((upvalues.len() - 1) as u8, variable)))
+    }
+
+    /// `while <condition> do <block> end`
+    /// LINK - https://craftinginterpreters.com/jumping-back-and-forth.html#while-statements
+    fn parse_while_statement(&mut self) -> Result<(), ParseError> {
+        let loop_start = self.chunk.code.len();
+        let condition_span = self.current_span;
+
+        self.parse_expression()?;
+        self.consume(Token::Do)?;
+
+        let exit_jump = self.emit_jump(ByteCode::JumpIfFalse, condition_span);
+        self.chunk.write_chunk(ByteCode::Pop as u8, condition_span);
+
+        self.begin_loop();
+        self.parse_scoped_block();
+        self.emit_loop(ByteCode::Loop, loop_start, self.current_span);
+
+        self.patch_jump(exit_jump);
+        self.chunk.write_chunk(ByteCode::Pop as u8, condition_span);
+        self.end_loop();
+
+        self.consume(Token::End)
+    }
+
+    /// `repeat <block> until <condition>`
+    ///
+    /// The condition is part of the block's scope, so it can use its locals.
+    fn parse_repeat_statement(&mut self) -> Result<(), ParseError> {
+        let loop_start = self.chunk.code.len();
+
+        self.begin_loop();
+        self.begin_scope();
+        self.parse_block();
+
+        let result = self.parse_repeat_condition(loop_start);
+
+        // NOTE - The locals were already discarded by the condition's code
+        self.scope_depth -= 1;
+        self.locals.truncate(self.locals_in_scope(self.scope_depth));
+        self.end_loop();
+
+        result
+    }
+
+    /// Compiles `until <condition>`, looping back while it's false
+    fn parse_repeat_condition(&mut self, loop_start: usize) -> Result<(), ParseError> {
+        self.consume(Token::Until)?;
+
+        let condition_span = self.current_span;
+        self.parse_expression()?;
+
+        // NOTE - The locals are discarded after the condition, in both of its branches
+        let repeat_jump = self.emit_jump(ByteCode::JumpIfFalse, condition_span);
+        self.chunk.write_chunk(ByteCode::Pop as u8, condition_span);
+        self.emit_locals_pop(self.scope_depth - 1, condition_span);
+        let exit_jump = self.emit_jump(ByteCode::Jump, condition_span);
+
+        self.patch_jump(repeat_jump);
+        self.chunk.write_chunk(ByteCode::Pop as u8, condition_span);
+        self.emit_locals_pop(self.scope_depth - 1, condition_span);
+        self.emit_loop(ByteCode::Loop, loop_start, condition_span);
+
+        self.patch_jump(exit_jump);
+
+        Ok(())
+    }
+
+    /// `for <name> = <start>, <limit> [, <step>] do <block> end` or
+    /// `for <name> {, <name>} in <iterator> [, <state> [, <control>]] do <block> end`
+    ///
+    /// The loop's state is kept in hidden locals, declared in a scope around
+    /// the loop variables.
+    fn parse_for_statement(&mut self) -> Result<(), ParseError> {
+        let (name, name_span) = self.parse_identifier()?;
+
+        self.begin_scope();
+        let result = match self.current_token {
+            Token::Assign => self.parse_numeric_for(name, name_span),
+            _ => self.parse_generic_for(name, name_span),
+        };
+        self.end_scope();
+
+        result
+    }
+
+    fn parse_numeric_for(&mut self, name: String, name_span: Span) -> Result<(), ParseError> {
+        self.consume(Token::Assign)?;
+        self.parse_expression()?;
+        self.consume(Token::Comma)?;
+        self.parse_expression()?;
+
+        if self.match_token(Token::Comma) {
+            self.parse_expression()?;
+        } else {
+            self.chunk.write_constant(ValueType::Int(1), name_span);
+        }
+
+        self.consume(Token::Do)?;
+        self.add_hidden_locals(&["(for counter)", "(for limit)", "(for step)"], name_span)?;
+
+        let prep_jump = self.emit_jump(ByteCode::ForPrep, name_span);
+        let body_start = self.chunk.code.len();
+
+        self.begin_loop();
+        self.begin_scope();
+        self.add_loop_variables(vec![(name, name_span)]);
+        self.parse_block();
+        self.end_scope();
+
+        self.emit_loop(ByteCode::ForLoop, body_start, self.current_span);
+        self.patch_jump(prep_jump);
+        self.end_loop();
+
+        self.consume(Token::End)
+    }
+
+    /// Like in Lua, the iterator is called with the state and control value
+    /// until it returns `nil`.
+    /// LINK - https://www.lua.org/manual/5.4/manual.html#3.3.5
+    fn parse_generic_for(&mut self, name: String, name_span: Span) -> Result<(), ParseError> {
+        let mut variables = vec![(name, name_span)];
+        while self.match_token(Token::Comma) {
+            variables.push(self.parse_identifier()?);
+        }
+
+        self.consume(Token::In)?;
+
+        // Iterator, state and control value, the missing ones being `nil`
+        let mut explist_count = 0;
+        loop {
+            self.parse_expression()?;
+            explist_count += 1;
+
+            if explist_count == 3 || !self.match_token(Token::Comma) {
+                break;
+            }
+        }
+
+        for _ in explist_count..3 {
+            self.chunk.write_chunk(ByteCode::Nil as u8, name_span);
+        }
+
+        self.consume(Token::Do)?;
+        self.add_hidden_locals(
+            &["(for iterator)", "(for state)", "(for control)"],
+            name_span,
+        )?;
+
+        let loop_start = self.chunk.code.len();
+        self.chunk.write_chunk(ByteCode::ForCall as u8, name_span);
+        self.chunk
+            .write_chunk(ByteCode::ForCallLoop as u8, name_span);
+        self.chunk.write_chunk(variables.len() as u8, name_span);
+        self.chunk.write_chunk(0xFF, name_span);
+        self.chunk.write_chunk(0xFF, name_span);
+        let exit_jump = self.chunk.code.len() - 2;
+
+        self.begin_loop();
+        self.begin_scope();
+        self.add_loop_variables(variables);
+        self.parse_block();
+        self.end_scope();
+
+        self.emit_loop(ByteCode::Loop, loop_start, self.current_span);
+        self.patch_jump(exit_jump);
+        self.end_loop();
+
+        self.consume(Token::End)
+    }
+
+    /// Declares the locals holding a loop's state, their names can't be written
+    /// in the source code so they can't be used
+    fn add_hidden_locals(&mut self, names: &[&str], span: Span) -> Result<(), ParseError> {
+        for name in names {
+            self.add_local(name.to_string(), Variable::default(), span)?;
+        }
+
+        Ok(())
+    }
+
+    /// Declares the immutable variables of a `for` loop, reporting the errors
+    /// right away so the loop's body still gets compiled
+    fn add_loop_variables(&mut self, variables: Vec<(String, Span)>) {
+        for (name, span) in variables {
+            if let Err(error) = self.add_local(name, Variable::default(), span) {
+                self.report(error);
+            }
+        }
+    }
+
+    /// Jumps out of the innermost loop, discarding the locals declared inside it
+    fn parse_break_statement(&mut self) -> Result<(), ParseError> {
+        let break_span = self.previus_span;
+        let Some(depth) = self.loops.last().map(|innermost| innermost.scope_depth) else {
+            return Err(ParseError::new(
+                ParseErrorType::BreakOutsideLoop,
+                break_span,
+            ));
+        };
+
+        self.emit_locals_pop(depth, break_span);
+        let break_jump = self.emit_jump(ByteCode::Jump, break_span);
+
+        if let Some(innermost) = self.loops.last_mut() {
+            innermost.break_jumps.push(break_jump);
+        }
+
+        Ok(())
+    }
+
+    fn begin_loop(&mut self) {
+        self.loops.push(Loop {
+            scope_depth: self.scope_depth,
+            break_jumps: Vec::new(),
+        });
+    }
+
+    /// Makes the `break` statements of the innermost loop land on the next instruction
+    fn end_loop(&mut self) {
+        if let Some(innermost) = self.loops.pop() {
+            for break_jump in innermost.break_jumps {
+                self.patch_jump(break_jump);
+            }
+        }
+    }
+
+    /// Writes a jump with a placeholder offset, returning where the offset is
+    /// so it can be patched once the jump's target is known
+    fn emit_jump(&mut self, jump: ByteCode, span: Span) -> usize {
+        self.chunk.write_chunk(jump as u8, span);
+        self.chunk.write_chunk(0xFF, span);
+        self.chunk.write_chunk(0xFF, span);
+
+        self.chunk.code.len() - 2
+    }
+
+    /// Makes the jump at the given offset land on the next instruction.
+    /// Errors are reported right away, since the code around the jump is fine.
+    fn patch_jump(&mut self, offset: usize) {
+        // NOTE - Counted from after the jump's operand
+        let jump = self.chunk.code.len() - offset - 2;
+
+        if jump > u16::MAX as usize {
+            let span = self.chunk.spans[offset];
+            self.report(ParseError::new(ParseErrorType::JumpTooLarge, span));
+
+            return;
+        }
+
+        self.chunk.code[offset] = (jump & 0xFF) as u8;
+        self.chunk.code[offset + 1] = ((jump >> 8) & 0xFF) as u8;
+    }
+
+    /// Writes a jump back to the start of a loop
+    fn emit_loop(&mut self, instruction: ByteCode, loop_start: usize, span: Span) {
+        self.chunk.write_chunk(instruction as u8, span);
+
+        // NOTE - Counted from after the loop's operand
+        let jump = self.chunk.code.len() - loop_start + 2;
+        if jump > u16::MAX as usize {
+            self.report(ParseError::new(ParseErrorType::JumpTooLarge, span));
+        }
+
+        self.chunk.write_chunk((jump & 0xFF) as u8, span);
+        self.chunk.write_chunk(((jump >> 8) & 0xFF) as u8, span);
+    }
+
+    fn parse_print_statement(&mut self) -> Result<(), ParseError> {
+        let print_span = self.previus_span;
+
+        self.parse_expression()?;
+        self.chunk.write_chunk(ByteCode::Print as u8, print_span);
+
+        Ok(())
+    }
+
+    fn parse_expression_statement(&mut self) -> Result<(), ParseError> {
+        self.can_assign = true;
+        self.parse_expression()?;
+        self.chunk
+            .write_chunk(ByteCode::Pop as u8, self.previus_span);
+
+        Ok(())
+    }
+
+    fn parse_precedence(&mut self, precedence: u8) -> Result<(), ParseError> {
+        // NOTE - Left in place, so the recovery resumes at the statement
+        // instead of skipping it
+        if self.starts_statement() {
+            return Err(ParseError::new(
+                ParseErrorType::ExpectedExpression,
+                self.current_span,
+            ));
+        }
+
+        let can_assign = mem::take(&mut self.can_assign);
+        self.advance();
+        let previous_tok_rule: &ParseRule<'_, R> = rules::get_rule(&self.previus_token);
+        let prefix = previous_tok_rule.prefix;
+
+        // NOTE - Only a variable sets it, so it's known to be the callee of a call right after it
+        self.callee_signature = None;
+
+        match prefix {
+            Some(prefix_fn) => prefix_fn(self)?,
+            None => {
+                return Err(ParseError::new(
+                    ParseErrorType::ExpectedExpression,
+                    self.previus_span,
+                ))
+            }
+        }
+
+        // NOTE - The rule must be fetched after every infix, since it moves the current token
+        while precedence <= rules::get_rule::<R>(&self.current_token).precedence as u8 {
+            self.advance();
+
+            // NOTE - Any other operator would compile the assigned index as its operand
+            self.can_assign = can_assign && matches!(self.previus_token, Token::SqurL | Token::Dot);
+
+            let infix = rules::get_rule::<R>(&self.previus_token).infix;
+            if let Some(infix_fn) = infix {
+                infix_fn(self)?;
+            }
+
+            self.callee_signature = None;
+        }
+
+        Ok(())
+    }
+
+    fn parse_expression(&mut self) -> Result<(), ParseError> {
+        self.parse_precedence(Precedence::Assignment as u8)
+    }
+
+    fn _parse_prefix_expression(&mut self) -> Result<(), ParseError> {
+        todo!()
+    }
+
+    fn parse_grouping(&mut self) -> Result<(), ParseError> {
+        self.parse_expression()?;
+        self.consume(Token::ParR)?;
+
+        Ok(())
+    }
+
+    fn parse_unary_op(&mut self) -> Result<(), ParseError> {
+        let sufix_operator = self.previus_token.clone();
+        let operator_span = self.previus_span;
+
+        // Compile operand
+        self.parse_precedence(Precedence::Unary as u8)?;
+
+        match sufix_operator {
+            Token::Sub => {
+                self.chunk
+                    .write_chunk(ByteCode::Negate as u8, operator_span);
+
+                Ok(())
+            }
+            Token::Not => {
+                self.chunk.write_chunk(ByteCode::Not as u8, operator_span);
+
+                Ok(())
+            }
+            Token::BitNot => {
+                self.chunk
+                    .write_chunk(ByteCode::BitNot as u8, operator_span);
+
+                Ok(())
+            }
+            Token::Len => {
+                self.chunk.write_chunk(ByteCode::Len as u8, operator_span);
+
+                Ok(())
+            }
+            _ => Ok(()), // unreachable
+        }
+    }
+
+    fn parse_binary_op(&mut self) -> Result<(), ParseError> {
+        let operator = self.previus_token.clone();
+        let operator_span = self.previus_span;
+        let rule: &ParseRule<'_, R> = rules::get_rule(&self.previus_token);
+
+        // NOTE - Exponentiation and concatenation are right associative,
+        // `2 ^ 3 ^ 2` being `2 ^ (3 ^ 2)`
+        match operator {
+            Token::Pow | Token::Concat => self.parse_precedence(rule.precedence as u8)?,
+            _ => self.parse_precedence(rule.precedence as u8 + 1)?,
+        }
+
+        match operator {
+            Token::Add => self.chunk.write_chunk(ByteCode::Add as u8, operator_span),
+            Token::Sub => self
+                .chunk
+                .write_chunk(ByteCode::Subtract as u8, operator_span),
+            Token::Mul => self
+                .chunk
+                .write_chunk(ByteCode::Multiply as u8, operator_span),
+            Token::Div => self
+                .chunk
+                .write_chunk(ByteCode::Divide as u8, operator_span),
+            Token::Mod => self
+                .chunk
+                .write_chunk(ByteCode::Modulo as u8, operator_span),
+            Token::Pow => self.chunk.write_chunk(ByteCode::Power as u8, operator_span),
+            Token::Idiv => self
+                .chunk
+                .write_chunk(ByteCode::IntDivide as u8, operator_span),
+            Token::BitAnd => self
+                .chunk
+                .write_chunk(ByteCode::BitAnd as u8, operator_span),
+            Token::BitOr => self.chunk.write_chunk(ByteCode::BitOr as u8, operator_span),
+            Token::BitNot => self
+                .chunk
+                .write_chunk(ByteCode::BitXor as u8, operator_span),
+            Token::ShiftL => self
+                .chunk
+                .write_chunk(ByteCode::ShiftLeft as u8, operator_span),
+            Token::ShiftR => self
+                .chunk
+                .write_chunk(ByteCode::ShiftRight as u8, operator_span),
+            Token::Concat => self
+                .chunk
+                .write_chunk(ByteCode::Concat as u8, operator_span),
+            Token::Equal => self.chunk.write_chunk(ByteCode::Equal as u8, operator_span),
+            Token::Less => self.chunk.write_chunk(ByteCode::Less as u8, operator_span),
+            Token::Greater => self
+                .chunk
+                .write_chunk(ByteCode::Greater as u8, operator_span),
+
+            // NOTE - Written as the complement of the other comparisons
+            Token::NotEq => self.emit_not(ByteCode::Equal, operator_span),
+            Token::LesEq => self.emit_not(ByteCode::Greater, operator_span),
+            Token::GreEq => self.emit_not(ByteCode::Less, operator_span),
+
+            _ => return Ok(()),
+        };
+
+        Ok(())
+    }
+
+    /// `<left> and <right>`, the right operand only being evaluated when the
+    /// left one is truthy. Like in Lua, it results in the deciding operand.
+    /// LINK - https://craftinginterpreters.com/jumping-back-and-forth.html#logical-operators
+    fn parse_and(&mut self) -> Result<(), ParseError> {
+        let operator_span = self.previus_span;
+        let end_jump = self.emit_jump(ByteCode::JumpIfFalse, operator_span);
+
+        self.chunk.write_chunk(ByteCode::Pop as u8, operator_span);
+        self.parse_precedence(Precedence::And as u8)?;
+        self.patch_jump(end_jump);
+
+        Ok(())
+    }
+
+    /// `<left> or <right>`, the right operand only being evaluated when the
+    /// left one is falsey
+    fn parse_or(&mut self) -> Result<(), ParseError> {
+        let operator_span = self.previus_span;
+        let else_jump = self.emit_jump(ByteCode::JumpIfFalse, operator_span);
+        let end_jump = self.emit_jump(ByteCode::Jump, operator_span);
+
+        self.patch_jump(else_jump);
+        self.chunk.write_chunk(ByteCode::Pop as u8, operator_span);
+        self.parse_precedence(Precedence::Or as u8)?;
+        self.patch_jump(end_jump);
+
+        Ok(())
+    }
+
+    /// Writes an instruction negating the result of the given one
+    fn emit_not(&mut self, instruction: ByteCode, span: Span) {
+        self.chunk.write_chunk(instruction as u8, span);
+        self.chunk.write_chunk(ByteCode::Not as u8, span);
+    }
+
+    fn parse_variable(&mut self) -> Result<(), ParseError> {
+        let Token::Identifier(name) = self.previus_token.clone() else {
+            return Ok(()); // unreachable
+        };
+
+        let (variable, location) = self.emit_get_variable(&name, self.previus_span)?;
+        if self.current_token == Token::ParL {
+            self.set_forward_usage(&name, location, GlobalUsage::Call);
+        }
+
+        self.callee_signature = variable.signature;
+
+        Ok(())
+    }
+
+    /// Pushes the value of the variable with the given name
+    fn emit_get_variable(
+        &mut self,
+        name: &str,
+        span: Span,
+    ) -> Result<(Variable, VariableLocation), ParseError> {
+        let (variable, location) = self.resolve_variable(name, span)?;
+
+        match location {
+            VariableLocation::Global => {
+                self.chunk.write_identifier(ByteCode::GetGlobal, name, span)
+            }
+            VariableLocation::Local(slot) => {
+                self.chunk.write_chunk(ByteCode::GetLocal as u8, span);
+                self.chunk.write_chunk(slot, span);
+            }
+            VariableLocation::Upvalue(index) => {
+                self.chunk.write_chunk(ByteCode::GetUpvalue as u8, span);
+                self.chunk.write_chunk(index, span);
+            }
+        }
+
+        Ok((variable, location))
+    }
+
+    /// `{<value>, <name> = <value>, [<key>] = <value>}`, the fields being
+    /// separated by `,` or `;`. Values without a key get consecutive int keys,
+    /// starting from `1`.
+    fn parse_table(&mut self) -> Result<(), ParseError> {
+        self.chunk
+            .write_chunk(ByteCode::NewTable as u8, self.previus_span);
+
+        let mut position = 0;
+        while self.current_token != Token::CurlyR {
+            let field_start = self.current_span;
+
+            if self.match_token(Token::SqurL) {
+                self.parse_expression()?;
+                self.consume(Token::SqurR)?;
+                self.consume(Token::Assign)?;
+            } else if self.is_assignment()? {
+                let (name, name_span) = self.parse_identifier()?;
+                self.emit_name_key(name, name_span);
+                self.consume(Token::Assign)?;
+            } else {
+                position += 1;
+                self.chunk
+                    .write_constant(ValueType::Int(position), field_start);
+            }
+
+            self.parse_expression()?;
+            self.chunk
+                .write_chunk(ByteCode::InitField as u8, field_start.to(self.previus_span));
+
+            if !self.match_token(Token::Comma) && !self.match_token(Token::SemiColon) {
+                break;
+            }
+        }
+
+        self.consume(Token::CurlyR)
+    }
+
+    /// `<table>[<key>]`
+    fn parse_index(&mut self) -> Result<(), ParseError> {
+        let can_assign = mem::take(&mut self.can_assign);
+        let index_start = self.previus_span;
+
+        self.parse_expression()?;
+        self.consume(Token::SqurR)?;
+
+        self.emit_index(can_assign, index_start.to(self.previus_span))
+    }
+
+    /// `<table>.<name>`, short for `<table>["<name>"]`
+    fn parse_field(&mut self) -> Result<(), ParseError> {
+        let can_assign = mem::take(&mut self.can_assign);
+        let (name, name_span) = self.parse_identifier()?;
+
+        self.emit_name_key(name, name_span);
+        self.emit_index(can_assign, name_span)
+    }
+
+    /// Pushes the name of a field as its key
+    fn emit_name_key(&mut self, name: String, span: Span) {
+        let key = ObjString::new(name);
+        self.chunk
+            .write_constant(ValueType::Object(Obj::String(Rc::new(key))), span);
+    }
+
+    /// Reads the value of the key, or assigns to it when it's the target of
+    /// an assignment statement
+    fn emit_index(&mut self, can_assign: bool, span: Span) -> Result<(), ParseError> {
+        if can_assign && self.match_token(Token::Assign) {
+            self.parse_expression()?;
+            self.chunk.write_chunk(ByteCode::SetIndex as u8, span);
+
+            return Ok(());
+        }
+
+        self.chunk.write_chunk(ByteCode::GetIndex as u8, span);
+
+        Ok(())
+    }
+
+    /// `<callee>(<arguments>)`
+    fn parse_call(&mut self) -> Result<(), ParseError> {
+        let call_span = self.previus_span;
+        let signature = self.callee_signature.take();
+        let mut argument_count = 0;
+
+        if self.current_token != Token::ParR {
+            argument_count = self.parse_arguments(signature)?;
+        }
+
+        self.consume(Token::ParR)?;
+        self.chunk.write_chunk(ByteCode::Call as u8, call_span);
+        self.chunk.write_chunk(argument_count as u8, call_span);
+
+        Ok(())
+    }
+
+    /// `<name> <arguments>`, a call statement without parentheses
+    fn parse_command_call(&mut self) -> Result<(), ParseError> {
+        let (name, name_span) = self.parse_identifier()?;
+        let (variable, location) = self.emit_get_variable(&name, name_span)?;
+        self.set_forward_usage(&name, location, GlobalUsage::Call);
+
+        let argument_count = self.parse_arguments(variable.signature)?;
+
+        self.chunk.write_chunk(ByteCode::Call as u8, name_span);
+        self.chunk.write_chunk(argument_count as u8, name_span);
+        self.chunk.write_chunk(ByteCode::Pop as u8, name_span);
+
+        Ok(())
+    }
+
+    /// Compiles comma separated arguments, returning how many there are.
+    /// Without a known signature, every argument is passed by value.
+    fn parse_arguments(&mut self, signature: Option<Rc<[Param]>>) -> Result<usize, ParseError> {
+        let mut argument_count = 0;
+
+        loop {
+            let param = signature
+                .as_ref()
+                .and_then(|params| params.get(argument_count));
+
+            match param {
+                Some(Param {
+                    data_type,
+                    mutable: true,
+                    ..
+                }) => self.parse_reference(*data_type)?,
+                _ => self.parse_expression()?,
+            }
+
+            if argument_count == ARGUMENTS_MAX {
+                return Err(ParseError::new(
+                    ParseErrorType::TooManyArguments,
+                    self.previus_span,
+                ));
+            }
+
+            argument_count += 1;
+
+            if !self.match_token(Token::Comma) {
+                return Ok(argument_count);
+            }
+        }
+    }
+
+    /// The argument of a `mut` parameter, which must be a mutable variable of
+    /// the same type. It's passed by reference, so the callee can assign to it.
+    fn parse_reference(&mut self, data_type: DataType) -> Result<(), ParseError> {
+        let argument_span = self.current_span;
+
+        if !matches!(&self.current_token, Token::Identifier(name) if DataType::from_name(name).is_none())
+        {
+            self.parse_expression()?;
+
+            return Err(ParseError::new(
+                ParseErrorType::ExpectedPlace,
+                argument_span.to(self.previus_span),
+            ));
+        }
+
+        let (name, name_span) = self.parse_identifier()?;
+
+        // NOTE - Anything else than a variable, like `a + 1`, has no place to assign to
+        if rules::get_rule::<R>(&self.current_token).precedence as u8 > Precedence::None as u8 {
+            return Err(ParseError::new(ParseErrorType::ExpectedPlace, name_span));
+        }
+
+        let (variable, location) = self.resolve_variable(&name, name_span)?;
+        self.set_forward_usage(&name, location, GlobalUsage::Reference(data_type));
+
+        if !variable.mutable {
+            return Err(ParseError::new(
+                ParseErrorType::ImmutableArgument { name },
+                name_span,
+            ));
+        }
+
+        match variable.data_type {
+            Some(found) if found != data_type => {
+                return Err(ParseError::new(
+                    ParseErrorType::MismatchedReference {
+                        name,
+                        expected: data_type,
+                        found,
+                    },
+                    name_span,
+                ))
+            }
+            _ => {}
+        }
+
+        match location {
+            VariableLocation::Global => {
+                self.chunk
+                    .write_identifier(ByteCode::RefGlobal, &name, name_span)
+            }
+            VariableLocation::Local(slot) => {
+                // NOTE - The reference shares the local the same way closures capture it
+                self.locals[slot as usize].is_captured = true;
+                self.chunk.write_chunk(ByteCode::RefLocal as u8, name_span);
+                self.chunk.write_chunk(slot, name_span);
+            }
+            VariableLocation::Upvalue(index) => {
+                self.chunk
+                    .write_chunk(ByteCode::RefUpvalue as u8, name_span);
+                self.chunk.write_chunk(index, name_span);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A string literal. Interpolated ones are compiled as the concatenation
+    /// of their segments, `"a {b} c"` being `"a " <> tostring(b) <> " c"`
+    fn parse_string(&mut self) -> Result<(), ParseError> {
+        self.emit_string_segment();
+
+        while let Token::Interpolation { .. } = self.previus_token {
+            let span = self.previus_span;
+
+            self.parse_expression()?;
+            self.chunk.write_chunk(ByteCode::ToString as u8, span);
+            self.chunk.write_chunk(ByteCode::Concat as u8, span);
+
+            // The lexer resumes the string after the expression's `}`
+            if !matches!(
+                self.current_token,
+                Token::String { .. } | Token::Interpolation { .. }
+            ) {
+                return Err(ParseError::new(
+                    ParseErrorType::ExpectedToken {
+                        expected: Token::CurlyR,
+                        found: self.current_token.clone(),
+                    },
+                    self.current_span,
+                ));
+            }
+
+            self.advance();
+            self.emit_string_segment();
+            self.chunk
+                .write_chunk(ByteCode::Concat as u8, self.previus_span);
+        }
+
+        Ok(())
+    }
+
+    fn emit_string_segment(&mut self) {
+        let (Token::String { value } | Token::Interpolation { value }) = &self.previus_token else {
+            return; // unreachable
+        };
+
+        let string = ObjString::new(value.as_str());
+        self.chunk.write_constant(
+            ValueType::Object(Obj::String(Rc::new(string))),
+            self.previus_span,
+        );
+    }
+
+    /// `nil`, `true` or `false`
+    fn parse_literal(&mut self) -> Result<(), ParseError> {
+        let literal = match self.previus_token {
+            Token::Nil => ByteCode::Nil,
+            Token::Bool { value: true } => ByteCode::True,
+            Token::Bool { value: false } => ByteCode::False,
+
+            _ => return Ok(()), // unreachable
+        };
+
+        self.chunk.write_chunk(literal as u8, self.previus_span);
+
+        Ok(())
+    }
+
+    fn parse_number(&mut self) -> Result<(), ParseError> {
+        let span = self.previus_span;
+
+        match self.previus_token {
+            Token::Int { value } => self.chunk.write_constant(ValueType::Int(value), span),
+            Token::Float { value } => self.chunk.write_constant(ValueType::Float(value), span),
+            Token::Byte { value } => self.chunk.write_constant(ValueType::Byte(value), span),
+
+            _ => return Ok(()),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parser_spans() {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load("1 +\n  20".as_bytes())
+            .expect("expression should compile");
+
+        // Constant 1, constant 20, then the addition at the `+` token
+        assert_eq!(chunk.get_line(&0), Some(&1));
+        assert_eq!(chunk.spans[2].line, 2);
+        assert_eq!(chunk.spans[2].column, 3);
+        assert_eq!(chunk.spans[4].start, 2);
+
+        let errors = Parser::new(&mut Chunk::new())
+            .load("1 +\n  )".as_bytes())
+            .expect_err("missing operand should fail");
+
+        assert!(matches!(
+            errors[0].error,
+            ParseErrorType::ExpectedExpression
+        ));
+        assert_eq!(
+            errors[0].span,
+            Span {
+                start: 6,
+                end: 7,
+                line: 2,
+                column: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parser_collects_every_error() {
+        let mut chunk = Chunk::new();
+        let errors = Parser::new(&mut chunk)
+            .load("1 + )\n2 $ 3\n4 * (".as_bytes())
+            .expect_err("program should fail to compile");
+
+        let found: Vec<(&ParseErrorType, i32)> = errors
+            .iter()
+            .map(|error| (&error.error, error.span.line))
+            .collect();
+
+        assert_eq!(found.len(), 3, "{found:?}");
+        assert!(matches!(found[0], (ParseErrorType::ExpectedExpression, 1)));
+        assert!(matches!(found[1], (ParseErrorType::LexError { .. }, 2)));
+        assert!(matches!(found[2], (ParseErrorType::ExpectedExpression, 3)));
+
+        // No runnable chunk is emitted
+        assert!(chunk.code.is_empty());
+        assert!(chunk.constants.is_empty());
+
+        // Statements after a missing expression are still compiled
+        let errors = Parser::new(&mut Chunk::new())
+            .load("int a =\nint b = 2\nprint b <> int\nprint 1 +\nprint b".as_bytes())
+            .expect_err("program should fail to compile");
+
+        let found: Vec<(&ParseErrorType, i32)> = errors
+            .iter()
+            .map(|error| (&error.error, error.span.line))
+            .collect();
+
+        assert_eq!(found.len(), 3, "{found:?}");
+        assert!(matches!(found[0], (ParseErrorType::ExpectedExpression, 2)));
+        assert!(matches!(found[1], (ParseErrorType::ExpectedExpression, 3)));
+        assert!(matches!(found[2], (ParseErrorType::ExpectedExpression, 5)));
+    }
+
+    #[test]
+    fn test_parser_statements() {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load("print 1\ndo 2 end;".as_bytes())
+            .expect("statements should compile");
+
+        assert_eq!(
+            chunk.code,
+            vec![
+                ByteCode::Constant as u8,
+                0,
+                ByteCode::Print as u8,
+                ByteCode::Constant as u8,
+                1,
+                ByteCode::Pop as u8,
+                ByteCode::Return as u8,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parser_block_errors() {
+        let errors = Parser::new(&mut Chunk::new())
+            .load("do print 1\nend end\ndo print 2".as_bytes())
+            .expect_err("unbalanced blocks should fail");
+
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(matches!(
+            errors[0].error,
+            ParseErrorType::UnexpectedToken { token: Token::End }
+        ));
+        assert!(matches!(
+            errors[1].error,
+            ParseErrorType::ExpectedToken {
+                expected: Token::End,
+                found: Token::EoS
+            }
+        ));
+    }
+
+    #[test]
+    fn test_parser_variable_declarations() {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load("mut int a = 1\na = a".as_bytes())
+            .expect("declarations should compile");
+
+        assert_eq!(chunk.identifiers, vec!["a".to_string()]);
+        assert_eq!(
+            chunk.code,
+            vec![
+                ByteCode::Constant as u8,
+                0,
+                ByteCode::CheckType as u8,
+                DataType::Int as u8,
+                ByteCode::DefineGlobal as u8,
+                0,
+                0,
+                ByteCode::GetGlobal as u8,
+                0,
+                0,
+                ByteCode::CheckType as u8,
+                DataType::Int as u8,
+                ByteCode::SetGlobal as u8,
+                0,
+                0,
+                ByteCode::Return as u8,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parser_variable_errors() {
+        let errors = Parser::new(&mut Chunk::new())
+            .load("int a = 1\na = 2\nprint b\nfloat a = 2.0\nmut a = 1\nint 2 = 1".as_bytes())
+            .expect_err("invalid variables should fail");
+
+        let found: Vec<(&ParseErrorType, i32)> = errors
+            .iter()
+            .map(|error| (&error.error, error.span.line))
+            .collect();
+
+        assert_eq!(found.len(), 5, "{found:?}");
+        assert!(matches!(
+            found[0],
+            (ParseErrorType::ImmutableAssignment { name }, 2) if name == "a"
+        ));
+        assert!(matches!(
+            found[1],
+            (ParseErrorType::UndeclaredVariable { name }, 3) if name == "b"
+        ));
+        assert!(matches!(
+            found[2],
+            (ParseErrorType::AlreadyDeclared { name }, 4) if name == "a"
+        ));
+        assert!(matches!(found[3], (ParseErrorType::ExpectedType { .. }, 5)));
+        assert!(matches!(
+            found[4],
+            (ParseErrorType::ExpectedIdentifier { .. }, 6)
+        ));
+    }
+
+    #[test]
+    fn test_parser_local_scopes() {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load("do int a = 1 do int a = a int b = a end print a end".as_bytes())
+            .expect("locals should compile");
+
+        let check_int = [ByteCode::CheckType as u8, DataType::Int as u8];
+        let expected: Vec<u8> = [
+            &[ByteCode::Constant as u8, 0][..],
+            &check_int,
+            // The inner `a` is initialized with the outer one, then shadows it
+            &[ByteCode::GetLocal as u8, 0],
+            &check_int,
+            &[ByteCode::GetLocal as u8, 1],
+            &check_int,
+            &[ByteCode::PopN as u8, 2],
+            &[ByteCode::GetLocal as u8, 0, ByteCode::Print as u8],
+            &[ByteCode::Pop as u8, ByteCode::Return as u8],
+        ]
+        .concat();
+
+        assert_eq!(chunk.code, expected);
+        assert!(chunk.identifiers.is_empty());
+    }
+
+    #[test]
+    fn test_parser_local_errors() {
+        let errors = Parser::new(&mut Chunk::new())
+            .load("do int a = 1\nint a = 2 end\nprint a".as_bytes())
+            .expect_err("invalid locals should fail");
+
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(matches!(
+            &errors[0].error,
+            ParseErrorType::AlreadyDeclared { name } if name == "a"
+        ));
+        assert!(matches!(
+            &errors[1].error,
+            ParseErrorType::UndeclaredVariable { name } if name == "a"
+        ));
+
+        let source: String = (0..=LOCALS_MAX)
+            .map(|i| format!("int a{i} = {i}\n"))
+            .collect();
+        let errors = Parser::new(&mut Chunk::new())
+            .load(format!("do\n{source}end").as_bytes())
+            .expect_err("too many locals should fail");
+
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(matches!(errors[0].error, ParseErrorType::TooManyLocals));
+        assert_eq!(errors[0].span.line, LOCALS_MAX as i32 + 2);
+    }
+
+    #[test]
+    fn test_parser_if_statement() {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load("if 1 then print 2 elseif 3 then print 4 else print 5 end".as_bytes())
+            .expect("if statement should compile");
+
+        let expected: Vec<u8> = [
+            &[ByteCode::Constant as u8, 0][..],
+            &[ByteCode::JumpIfFalse as u8, 7, 0],
+            &[
+                ByteCode::Pop as u8,
+                ByteCode::Constant as u8,
+                1,
+                ByteCode::Print as u8,
+            ],
+            &[ByteCode::Jump as u8, 17, 0],
+            &[ByteCode::Pop as u8, ByteCode::Constant as u8, 2],
+            &[ByteCode::JumpIfFalse as u8, 7, 0],
+            &[
+                ByteCode::Pop as u8,
+                ByteCode::Constant as u8,
+                3,
+                ByteCode::Print as u8,
+            ],
+            &[ByteCode::Jump as u8, 4, 0],
+            &[
+                ByteCode::Pop as u8,
+                ByteCode::Constant as u8,
+                4,
+                ByteCode::Print as u8,
+            ],
+            &[ByteCode::Return as u8],
+        ]
+        .concat();
+
+        assert_eq!(chunk.code, expected);
+    }
+
+    #[test]
+    fn test_parser_if_errors() {
+        let errors = Parser::new(&mut Chunk::new())
+            .load("if 1 print 2 end\nif 1 then else print 2 elseif 3 then end".as_bytes())
+            .expect_err("malformed if statements should fail");
+
+        assert!(matches!(
+            errors[0].error,
+            ParseErrorType::ExpectedToken {
+                expected: Token::Then,
+                ..
+            }
+        ));
+        assert!(errors.iter().any(|error| matches!(
+            error.error,
+            ParseErrorType::ExpectedToken {
+                expected: Token::End,
+                found: Token::ElseIf
+            }
+        )));
+
+        let body = "print 1\n".repeat(u16::MAX as usize / 4);
+        let errors = Parser::new(&mut Chunk::new())
+            .load(format!("if 1 then\n{body}end").as_bytes())
+            .expect_err("too large branch should fail");
+
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(matches!(errors[0].error, ParseErrorType::JumpTooLarge));
+        assert_eq!(errors[0].span.line, 1);
+    }
+
+    #[test]
+    fn test_parser_loops() {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load("while 1 do int a = 2 break end".as_bytes())
+            .expect("while loop should compile");
+
+        let expected: Vec<u8> = [
+            &[ByteCode::Constant as u8, 0][..],
+            &[ByteCode::JumpIfFalse as u8, 13, 0, ByteCode::Pop as u8],
+            &[ByteCode::Constant as u8, 1],
+            &[ByteCode::CheckType as u8, DataType::Int as u8],
+            // `break` discards `a` before jumping past the loop's `Pop`
+            &[ByteCode::Pop as u8, ByteCode::Jump as u8, 5, 0],
+            &[ByteCode::Pop as u8, ByteCode::Loop as u8, 18, 0],
+            &[ByteCode::Pop as u8, ByteCode::Return as u8],
+        ]
+        .concat();
+
+        assert_eq!(chunk.code, expected);
+
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load("repeat int a = 1 until a".as_bytes())
+            .expect("repeat loop should compile");
+
+        let expected: Vec<u8> = [
+            &[ByteCode::Constant as u8, 0][..],
+            &[ByteCode::CheckType as u8, DataType::Int as u8],
+            &[ByteCode::GetLocal as u8, 0],
+            &[ByteCode::JumpIfFalse as u8, 5, 0],
+            &[
+                ByteCode::Pop as u8,
+                ByteCode::Pop as u8,
+                ByteCode::Jump as u8,
+                5,
+                0,
+            ],
+            &[
+                ByteCode::Pop as u8,
+                ByteCode::Pop as u8,
+                ByteCode::Loop as u8,
+                19,
+                0,
+            ],
+            &[ByteCode::Return as u8],
+        ]
+        .concat();
+
+        assert_eq!(chunk.code, expected);
+    }
+
+    #[test]
+    fn test_parser_break_outside_loop() {
+        let errors = Parser::new(&mut Chunk::new())
+            .load("break\nwhile 1 do break end\ndo break end".as_bytes())
+            .expect_err("break outside of loops should fail");
+
+        let lines: Vec<i32> = errors.iter().map(|error| error.span.line).collect();
+
+        assert_eq!(lines, vec![1, 3]);
+        assert!(errors
+            .iter()
+            .all(|error| matches!(error.error, ParseErrorType::BreakOutsideLoop)));
+    }
+
+    #[test]
+    fn test_parser_for_errors() {
+        let errors = Parser::new(&mut Chunk::new())
+            .load("for i = 1, 2 do i = 3 end\nfor k, v in 1 do v = 2 end\nprint i".as_bytes())
+            .expect_err("invalid for loops should fail");
+
+        let found: Vec<(&ParseErrorType, i32)> = errors
+            .iter()
+            .map(|error| (&error.error, error.span.line))
+            .collect();
+
+        assert_eq!(found.len(), 3, "{found:?}");
+        assert!(matches!(
+            found[0],
+            (ParseErrorType::ImmutableAssignment { name }, 1) if name == "i"
+        ));
+        assert!(matches!(
+            found[1],
+            (ParseErrorType::ImmutableAssignment { name }, 2) if name == "v"
+        ));
+        assert!(matches!(
+            found[2],
+            (ParseErrorType::UndeclaredVariable { name }, 3) if name == "i"
+        ));
+    }
+
+    #[test]
+    fn test_parser_functions() {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load("function add(int a, int b) -> int return a + b end\nprint add(1, 2)".as_bytes())
+            .expect("function should compile");
+
+        let function = match &chunk.constants[0] {
+            ValueType::Object(Obj::Function(function)) => function,
+            constant => panic!("expected a function, got {constant:?}"),
+        };
+
+        assert_eq!(
+            function.params,
+            vec![
+                Param {
+                    data_type: DataType::Int,
+                    nullable: false,
+                    mutable: false
+                };
+                2
+            ]
+        );
+        assert_eq!(
+            function.return_type,
+            Some(ReturnType {
+                data_type: DataType::Int,
+                nullable: false
+            })
+        );
+        assert_eq!(
+            function.chunk.code[..8],
+            [
+                ByteCode::GetLocal as u8,
+                1,
+                ByteCode::GetLocal as u8,
+                2,
+                ByteCode::Add as u8,
+                ByteCode::CheckType as u8,
+                DataType::Int as u8,
+                ByteCode::Return as u8,
+            ]
+        );
+
+        let errors = Parser::new(&mut Chunk::new())
+            .load(
+                "function f() return 1 end
+                return 2
+                function g(int a, int a) break end
+                function h() break end
+                function i(int a) end
+                function j() -> int return end
+                print a"
+                    .as_bytes(),
+            )
+            .expect_err("invalid functions should fail");
+
+        let found: Vec<(&ParseErrorType, i32)> = errors
+            .iter()
+            .map(|error| (&error.error, error.span.line))
+            .collect();
+
+        assert_eq!(found.len(), 7, "{found:?}");
+        assert!(matches!(
+            found[0],
+            (ParseErrorType::UnexpectedReturnValue, 1)
+        ));
+        assert!(matches!(
+            found[1],
+            (ParseErrorType::UnexpectedReturnValue, 2)
+        ));
+        assert!(matches!(
+            found[2],
+            (ParseErrorType::AlreadyDeclared { .. }, 3)
+        ));
+        assert!(matches!(found[3], (ParseErrorType::BreakOutsideLoop, 3)));
+        assert!(matches!(found[4], (ParseErrorType::BreakOutsideLoop, 4)));
+        assert!(matches!(
+            found[5],
+            (
+                ParseErrorType::MissingReturnValue {
+                    expected: DataType::Int
+                },
+                6
+            )
+        ));
+        assert!(matches!(
+            found[6],
+            (ParseErrorType::UndeclaredVariable { .. }, 7)
+        ));
+    }
+
+    #[test]
+    fn test_parser_tables() {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load("table t = {5}\nt.x = t[1]".as_bytes())
+            .expect("tables should compile");
+
+        assert_eq!(
+            chunk.code[..7],
+            [
+                ByteCode::NewTable as u8,
+                ByteCode::Constant as u8,
+                0,
+                ByteCode::Constant as u8,
+                1,
+                ByteCode::InitField as u8,
+                ByteCode::CheckType as u8,
+            ]
+        );
+        assert_eq!(chunk.constants[0], ValueType::Int(1));
+
+        // The assigned value is left in the stack, like any expression statement
+        let assignment = chunk.code.len() - 14;
+        assert_eq!(
+            chunk.code[assignment..],
+            [
+                ByteCode::GetGlobal as u8,
+                0,
+                0,
+                ByteCode::Constant as u8,
+                2,
+                ByteCode::GetGlobal as u8,
+                0,
+                0,
+                ByteCode::Constant as u8,
+                3,
+                ByteCode::GetIndex as u8,
+                ByteCode::SetIndex as u8,
+                ByteCode::Pop as u8,
+                ByteCode::Return as u8,
+            ]
+        );
+
+        let errors = Parser::new(&mut Chunk::new())
+            .load(
+                "table t = {}
+                print t[1] = 2
+                t[1] + t.x = 2
+                table u = {1 2}"
+                    .as_bytes(),
+            )
+            .expect_err("only statements assign to an index");
+
+        let lines: Vec<i32> = errors.iter().map(|error| error.span.line).collect();
+        assert_eq!(lines, [2, 3, 4]);
+    }
+
+    #[test]
+    fn test_parser_forward_globals() {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load(
+                "function f() g(1) end
+                function g(int a) f() end"
+                    .as_bytes(),
+            )
+            .expect("functions should use globals declared after them");
+
+        let errors = Parser::new(&mut Chunk::new())
+            .load(
+                "function h(mut int a) end
+                function f()
+                    print missing
+                    constant = 2
+                    h(number)
+                    h(constant)
+                    mutate(number)
+                    mutate number
+                end
+                int constant = 1
+                mut float number = 1
+                function mutate(mut float a) end
+                print later"
+                    .as_bytes(),
+            )
+            .expect_err("invalid forward references should fail");
+
+        let found: Vec<(&ParseErrorType, i32)> = errors
+            .iter()
+            .map(|error| (&error.error, error.span.line))
+            .collect();
+
+        // Checked once the globals are declared, but reported in source order
+        assert_eq!(found.len(), 7, "{found:?}");
+        assert!(matches!(
+            found[0],
+            (ParseErrorType::UndeclaredVariable { name }, 3) if name == "missing"
+        ));
+        assert!(matches!(
+            found[1],
+            (ParseErrorType::ImmutableAssignment { name }, 4) if name == "constant"
+        ));
+        assert!(matches!(
+            found[2],
+            (ParseErrorType::MismatchedReference { .. }, 5)
+        ));
+        assert!(matches!(
+            found[3],
+            (ParseErrorType::ImmutableArgument { name }, 6) if name == "constant"
+        ));
+        assert!(matches!(
+            found[4],
+            (ParseErrorType::ForwardMutableCall { name }, 7) if name == "mutate"
+        ));
+        assert!(matches!(
+            found[5],
+            (ParseErrorType::ForwardMutableCall { name }, 8) if name == "mutate"
+        ));
+        assert!(matches!(
+            found[6],
+            (ParseErrorType::UndeclaredVariable { name }, 13) if name == "later"
+        ));
+    }
+
+    #[test]
+    fn test_parser_mut_params() {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load(
+                "function f(mut int a, int b) end
+                mut int c = 1
+                f(c, c)
+                function g(mut int d) f d, 2 end"
+                    .as_bytes(),
+            )
+            .expect("mutable arguments should compile");
+
+        // The first argument is a reference, the second a copy
+        let call_start = chunk.code.len() - 20;
+        assert_eq!(
+            chunk.code[call_start..call_start + 8],
+            [
+                ByteCode::GetGlobal as u8,
+                0,
+                0,
+                ByteCode::RefGlobal as u8,
+                1,
+                0,
+                ByteCode::GetGlobal as u8,
+                1,
+            ]
+        );
+
+        let g = match chunk.constants.last() {
+            Some(ValueType::Object(Obj::Function(function))) => function,
+            constant => panic!("expected a function, got {constant:?}"),
+        };
+        assert_eq!(g.chunk.code[3..5], [ByteCode::RefLocal as u8, 1]);
+
+        let errors = Parser::new(&mut Chunk::new())
+            .load(
+                "function f(mut int a) end
+                int b = 1
+                mut float c = 1
+                mut int d = 1
+                f(b)
+                f(c)
+                f(d + 1)
+                f(1)
+                f d"
+                .as_bytes(),
+            )
+            .expect_err("invalid mutable arguments should fail");
+
+        let found: Vec<(&ParseErrorType, i32)> = errors
+            .iter()
+            .map(|error| (&error.error, error.span.line))
+            .collect();
+
+        assert_eq!(found.len(), 4, "{found:?}");
+        assert!(matches!(
+            found[0],
+            (ParseErrorType::ImmutableArgument { .. }, 5)
+        ));
+        assert!(matches!(
+            found[1],
+            (
+                ParseErrorType::MismatchedReference {
+                    expected: DataType::Int,
+                    found: DataType::Float,
+                    ..
+                },
+                6
+            )
+        ));
+        assert!(matches!(found[2], (ParseErrorType::ExpectedPlace, 7)));
+        assert!(matches!(found[3], (ParseErrorType::ExpectedPlace, 8)));
+    }
+
+    #[test]
+    fn test_parser_closures() {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load(
+                "do
+                    int a = 1
+                    function f() print a end
+                end"
+                .as_bytes(),
+            )
+            .expect("closure should compile");
+
+        // `f` captures the local in slot 0, which is closed instead of popped
+        assert_eq!(
+            chunk.code[chunk.code.len() - 7..],
+            [
+                ByteCode::Closure as u8,
+                1,
+                1,
+                0,
+                ByteCode::Pop as u8,
+                ByteCode::CloseUpvalue as u8,
+                ByteCode::Return as u8,
+            ]
+        );
+
+        let f = match chunk.constants.last() {
+            Some(ValueType::Object(Obj::Function(function))) => function,
+            constant => panic!("expected a function, got {constant:?}"),
+        };
+        assert_eq!(f.chunk.code[..2], [ByteCode::GetUpvalue as u8, 0]);
+
+        let errors = Parser::new(&mut Chunk::new())
+            .load(
+                "function f()
+                    int a = 1
+                    mut int b = 2
+                    function g() b = 3 a = 4 end
+                end"
+                .as_bytes(),
+            )
+            .expect_err("captured immutable locals can't be assigned");
+
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(matches!(
+            &errors[0].error,
+            ParseErrorType::ImmutableAssignment { name } if name == "a"
+        ));
+    }
+
+    #[test]
+    fn test_parser_comparisons() {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load("print 1 + 2 <= 3 == 4 ~= 5".as_bytes())
+            .expect("comparisons should compile");
+
+        // (((1 + 2) <= 3) == 4) ~= 5, the complements being negated
+        assert_eq!(chunk.code[4], ByteCode::Add as u8);
+        assert_eq!(
+            chunk.code[7..10],
+            [
+                ByteCode::Greater as u8,
+                ByteCode::Not as u8,
+                ByteCode::Constant as u8
+            ]
+        );
+        assert_eq!(chunk.code[11], ByteCode::Equal as u8);
+        assert_eq!(
+            chunk.code[14..17],
+            [
+                ByteCode::Equal as u8,
+                ByteCode::Not as u8,
+                ByteCode::Print as u8
+            ]
+        );
+    }
 }
@@ -22,28 +22,31 @@ macro_rules! parse_rule {
 
 pub const RULES_COUNT: usize = (TokenRule::EoS as usize) + 1;
 
+// NOTE - Levels without an operator yet are kept to preserve the precedence ladder
+#[allow(dead_code)]
 #[repr(u8)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub enum Precedence {
+    #[default]
     None,
     Assignment, // = ->
     Or,         // or
     And,        // and
-    Equality,   // == !=
+    Equality,   // == ~=
     Comparison, // < > <= >=
+    BitOr,      // |
+    BitXor,     // ~
+    BitAnd,     // &
+    Shift,      // << >>
+    Concat,     // <>
     Term,       // + -
-    Factor,     // * /
+    Factor,     // * / // %
     Unary,      // - ~ not #
+    Power,      // ^
     Call,       // : . ()
     Primary,
 }
 
-impl Default for Precedence {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
 pub struct ParseRule<'a, R: Read> {
     pub prefix: Option<ParseFn<'a, R>>,
     pub infix: Option<ParseFn<'a, R>>,
@@ -90,7 +93,11 @@ impl<'a, R: Read> ParseRule<'a, R> {
     pub fn rules() -> &'a [ParseRule<'a, R>; RULES_COUNT] {
         &[
             // And
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| parser.parse_and()),
+                Precedence::And
+            ),
             // Do
             parse_rule!(None, None, Precedence::None),
             // Then,
@@ -112,11 +119,23 @@ impl<'a, R: Read> ParseRule<'a, R> {
             // Mut,
             parse_rule!(None, None, Precedence::None),
             // Nil,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                Some(|parser: &mut Parser<'_, R>| parser.parse_literal()),
+                None,
+                Precedence::None
+            ),
             // Not,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_unary_op() }),
+                None,
+                Precedence::None
+            ),
             // Or,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| parser.parse_or()),
+                Precedence::Or
+            ),
             // While,
             parse_rule!(None, None, Precedence::None),
             // Repeat,
@@ -129,6 +148,8 @@ impl<'a, R: Read> ParseRule<'a, R> {
             parse_rule!(None, None, Precedence::None),
             // Break,
             parse_rule!(None, None, Precedence::None),
+            // Print,
+            parse_rule!(None, None, Precedence::None),
             // Add,
             parse_rule!(
                 None,
@@ -154,51 +175,119 @@ impl<'a, R: Read> ParseRule<'a, R> {
                 Precedence::Factor
             ),
             // Mod,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_binary_op() }),
+                Precedence::Factor
+            ),
             // Pow,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_binary_op() }),
+                Precedence::Power
+            ),
             // Len,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                Some(|parser: &mut Parser<'_, R>| parser.parse_unary_op()),
+                None,
+                Precedence::None
+            ),
             // BitAnd,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_binary_op() }),
+                Precedence::BitAnd
+            ),
             // BitOr,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_binary_op() }),
+                Precedence::BitOr
+            ),
             // BitNot,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_unary_op() }),
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_binary_op() }),
+                Precedence::BitXor
+            ),
             // ShiftL,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_binary_op() }),
+                Precedence::Shift
+            ),
             // ShiftR,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_binary_op() }),
+                Precedence::Shift
+            ),
             // Idiv,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_binary_op() }),
+                Precedence::Factor
+            ),
             // Equal,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_binary_op() }),
+                Precedence::Equality
+            ),
             // NotEq,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_binary_op() }),
+                Precedence::Equality
+            ),
             // LesEq,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_binary_op() }),
+                Precedence::Comparison
+            ),
             // GreEq,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_binary_op() }),
+                Precedence::Comparison
+            ),
             // Less,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_binary_op() }),
+                Precedence::Comparison
+            ),
             // Greater,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_binary_op() }),
+                Precedence::Comparison
+            ),
             // Assign,
             parse_rule!(None, None, Precedence::None),
             // ParL,
             parse_rule!(
                 Some(|parser: &mut Parser<'_, R>| parser.parse_grouping()),
-                None,
-                Precedence::None
+                Some(|parser: &mut Parser<'_, R>| parser.parse_call()),
+                Precedence::Call
             ),
             // ParR,
             parse_rule!(None, None, Precedence::None),
             // CurlyL,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                Some(|parser: &mut Parser<'_, R>| parser.parse_table()),
+                None,
+                Precedence::None
+            ),
             // CurlyR,
             parse_rule!(None, None, Precedence::None),
             // SqurL,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| parser.parse_index()),
+                Precedence::Call
+            ),
             // SqurR,
             parse_rule!(None, None, Precedence::None),
             // DoubColon,
@@ -210,13 +299,23 @@ impl<'a, R: Read> ParseRule<'a, R> {
             // Comma,
             parse_rule!(None, None, Precedence::None),
             // Dot,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| parser.parse_field()),
+                Precedence::Call
+            ),
             // Concat,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                None,
+                Some(|parser: &mut Parser<'_, R>| { parser.parse_binary_op() }),
+                Precedence::Concat
+            ),
             // Dots,
             parse_rule!(None, None, Precedence::None),
             // Arrow,
             parse_rule!(None, None, Precedence::None),
+            // Question,
+            parse_rule!(None, None, Precedence::None),
             // Int,
             parse_rule!(
                 Some(|parser: &mut Parser<'_, R>| parser.parse_number()),
@@ -230,9 +329,23 @@ impl<'a, R: Read> ParseRule<'a, R> {
                 Precedence::None
             ),
             // String,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                Some(|parser: &mut Parser<'_, R>| parser.parse_string()),
+                None,
+                Precedence::None
+            ),
+            // Interpolation,
+            parse_rule!(
+                Some(|parser: &mut Parser<'_, R>| parser.parse_string()),
+                None,
+                Precedence::None
+            ),
             // Bool,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                Some(|parser: &mut Parser<'_, R>| parser.parse_literal()),
+                None,
+                Precedence::None
+            ),
             // Byte,
             parse_rule!(
                 Some(|parser: &mut Parser<'_, R>| parser.parse_number()),
@@ -240,7 +353,11 @@ impl<'a, R: Read> ParseRule<'a, R> {
                 Precedence::None
             ),
             // Identifier,
-            parse_rule!(None, None, Precedence::None),
+            parse_rule!(
+                Some(|parser: &mut Parser<'_, R>| parser.parse_variable()),
+                None,
+                Precedence::None
+            ),
             // EoS,
             parse_rule!(None, None, Precedence::None),
         ]
@@ -1,44 +1,467 @@
-use std::{fs::File, io::BufReader};
+use std::{fs::File, io::BufReader, path::Path};
 
-use crate::parser::tokens::{Token, TOKENS_MOCK};
+use crate::parser::{
+    errors::LexicalErrorType,
+    tokens::{tokens_mock, Span, Token},
+};
 
 use super::Lexer;
 
-// TODO - Finish writing this test
+fn lex_source(source: &str) -> Lexer<&[u8]> {
+    Lexer::new(source.as_bytes())
+}
+
 #[test]
 fn test_lexer_tokens() {
-    let current_dir = std::env::current_dir().expect("Failed to get current directory");
-    let relative_path = "..\\tests\\lang\\test_lexer.poke";
-    let file_path = current_dir.join(relative_path);
+    let file_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../tests/lang/test_lexer.poke");
 
     let file = File::open(file_path).expect("Failed to open test file");
     let mut lexer = Lexer::new(BufReader::new(file));
 
-    let mut previus_line = lexer.current_line;
+    for token in tokens_mock() {
+        lexer.expect(token);
+    }
+
+    lexer.expect(Token::EoS);
+}
+
+#[test]
+fn test_lexer_radix_numbers() {
+    let mut lexer =
+        lex_source("0x1F 0XaB 0o755 0b1010_1010 0xFFFF_FFFF 0x7F_b 0o377b 0b1111b 0x1B 0xaB 0xB");
+
+    lexer.expect(Token::Int { value: 0x1F });
+    lexer.expect(Token::Int { value: 0xAB });
+    lexer.expect(Token::Int { value: 0o755 });
+    lexer.expect(Token::Int { value: 0b1010_1010 });
+    lexer.expect(Token::Int { value: -1 });
+    lexer.expect(Token::Byte { value: 0x7F });
+    lexer.expect(Token::Byte { value: 0o377 });
+    lexer.expect(Token::Byte { value: 0b1111 });
+    lexer.expect(Token::Int { value: 0x1B });
+    lexer.expect(Token::Int { value: 0xAB });
+    lexer.expect(Token::Int { value: 0xB });
+    lexer.expect(Token::EoS);
+}
+
+#[test]
+fn test_lexer_radix_errors() {
+    let cases = [
+        ("0x", "0x", false),
+        ("0x1_0000_0000", "0x100000000", true),
+        ("0x100_b", "0x100_b", true),
+        ("0x_b", "0x_b", false),
+        ("0xFFb", "0xFFb", false),
+        ("0xab", "0xab", false),
+        ("0xFF_", "0xFF_", false),
+        ("0o7_", "0o7_", false),
+        ("0b102", "0b102", false),
+        ("0o8", "0o8", false),
+        ("0xFG", "0xFG", false),
+    ];
+
+    for (source, expected_number, is_overflow) in cases {
+        let error = lex_source(source)
+            .advance()
+            .expect_err("radix literal should fail to lex");
+
+        match error.error {
+            LexicalErrorType::IntegerOverflow { number } if is_overflow => {
+                assert_eq!(number, expected_number)
+            }
+            LexicalErrorType::MalformedNumber { number } if !is_overflow => {
+                assert_eq!(number, expected_number)
+            }
+            error => panic!("unexpected error for {source:?}: {error:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_lexer_number_signs() {
+    let mut lexer = lex_source("1-2 3e-2 4+5");
+
+    lexer.expect(Token::Int { value: 1 });
     lexer.expect(Token::Sub);
+    lexer.expect(Token::Int { value: 2 });
+    lexer.expect(Token::Float { value: 3e-2 });
+    lexer.expect(Token::Int { value: 4 });
+    lexer.expect(Token::Add);
+    lexer.expect(Token::Int { value: 5 });
+    lexer.expect(Token::EoS);
+}
+
+/// Source, expected error and span of the failing token as (line, column, start, end)
+type ErrorCase = (
+    &'static str,
+    fn(&LexicalErrorType) -> bool,
+    (i32, i32, usize, usize),
+);
+
+#[test]
+fn test_lexer_errors() {
+    let cases: [ErrorCase; 15] = [
+        (
+            "  \"\\xZ1\"",
+            |error| matches!(error, LexicalErrorType::BadHexEscape),
+            (1, 3, 2, 6),
+        ),
+        (
+            "\n  \"\\x4",
+            |error| matches!(error, LexicalErrorType::BadHexEscape),
+            (2, 3, 3, 7),
+        ),
+        (
+            "'\\256'",
+            |error| {
+                matches!(
+                    error,
+                    LexicalErrorType::DecimalEscapeTooLarge { value: 256 }
+                )
+            },
+            (1, 1, 0, 5),
+        ),
+        (
+            "'\\q'",
+            |error| matches!(error, LexicalErrorType::BadStringEscape),
+            (1, 1, 0, 3),
+        ),
+        (
+            "'abc",
+            |error| matches!(error, LexicalErrorType::UnexpectedStringEnd),
+            (1, 1, 0, 4),
+        ),
+        (
+            "'\\u{D800}'",
+            |error| matches!(error, LexicalErrorType::BadUnicodeEscape),
+            (1, 1, 0, 9),
+        ),
+        (
+            "'\\u{}'",
+            |error| matches!(error, LexicalErrorType::BadUnicodeEscape),
+            (1, 1, 0, 5),
+        ),
+        (
+            "'\\xC3'",
+            |error| matches!(error, LexicalErrorType::InvalidUtf8),
+            (1, 1, 0, 6),
+        ),
+        (
+            " → b",
+            |error| matches!(error, LexicalErrorType::UnexpectedToken { token: '→' }),
+            (1, 2, 1, 4),
+        ),
+        (
+            "[=x",
+            |error| matches!(error, LexicalErrorType::InvalidLongBracket),
+            (1, 1, 0, 2),
+        ),
+        (
+            "[==[a]=]\n]]",
+            |error| matches!(error, LexicalErrorType::UnterminatedLongString),
+            (1, 1, 0, 11),
+        ),
+        (
+            " --[[\n",
+            |error| matches!(error, LexicalErrorType::UnterminatedComment),
+            (1, 2, 1, 6),
+        ),
+        (
+            "  1.2.3",
+            |error| matches!(error, LexicalErrorType::MalformedNumber { .. }),
+            (1, 3, 2, 7),
+        ),
+        (
+            "2147483648",
+            |error| matches!(error, LexicalErrorType::IntegerOverflow { .. }),
+            (1, 1, 0, 10),
+        ),
+        (
+            "\n256b",
+            |error| matches!(error, LexicalErrorType::IntegerOverflow { .. }),
+            (2, 1, 1, 5),
+        ),
+    ];
 
-    for i in 1..TOKENS_MOCK.len() {
-        println!("{i}");
-        println!("AAAAAAAAA {:?}", TOKENS_MOCK[i]);
-        // +
-        lexer.expect(TOKENS_MOCK[i].clone());
+    for (source, is_expected_error, (line, column, start, end)) in cases {
+        let error = lex_source(source)
+            .advance()
+            .expect_err("source should fail to lex");
 
-        // *
-        let token = lexer.advance().expect("expected token");
-        if token == Token::EoS {
-            println!("END STREAM");
+        assert!(is_expected_error(&error.error), "{source:?}: {error:?}");
+        assert_eq!(
+            error.span,
+            Span {
+                start,
+                end,
+                line,
+                column
+            },
+            "{source:?}"
+        );
+    }
+}
+
+#[test]
+fn test_lexer_interpolation() {
+    let mut lexer = lex_source("\"a {b + {1}} c {'d{e}'}\\{f}\" {}");
+    let segment = |value: &str| Token::Interpolation {
+        value: value.to_string(),
+    };
+    let string = |value: &str| Token::String {
+        value: value.to_string(),
+    };
 
-            break;
+    lexer.expect(segment("a "));
+    lexer.expect(Token::Identifier("b".to_string()));
+    lexer.expect(Token::Add);
+    lexer.expect(Token::CurlyL);
+    lexer.expect(Token::Int { value: 1 });
+    lexer.expect(Token::CurlyR);
+    lexer.expect(segment(" c "));
+    lexer.expect(segment("d"));
+    lexer.expect(Token::Identifier("e".to_string()));
+    lexer.expect(string(""));
+    lexer.expect(string("{f}"));
+    lexer.expect(Token::CurlyL);
+    lexer.expect(Token::CurlyR);
+    lexer.expect(Token::EoS);
+
+    // Reported at the segment opening the interpolation, once the input ends
+    let mut lexer = lex_source("'a {b} {c");
+    lexer.expect(segment("a "));
+    lexer.expect(Token::Identifier("b".to_string()));
+    lexer.expect(segment(" "));
+    lexer.expect(Token::Identifier("c".to_string()));
+
+    let error = lexer
+        .advance()
+        .expect_err("interpolation should be unterminated");
+    assert!(matches!(
+        error.error,
+        LexicalErrorType::UnterminatedInterpolation
+    ));
+    assert_eq!(
+        error.span,
+        Span {
+            start: 5,
+            end: 8,
+            line: 1,
+            column: 6
         }
+    );
+    lexer.expect(Token::EoS);
+
+    // A string left open inside it also means the `}` is missing
+    let mut lexer = lex_source("\"a {b \"");
+    lexer.expect(segment("a "));
+    lexer.expect(Token::Identifier("b".to_string()));
+
+    let error = lexer
+        .advance()
+        .expect_err("interpolation should be unterminated");
+    assert!(matches!(
+        error.error,
+        LexicalErrorType::UnterminatedInterpolation
+    ));
+    lexer.expect(Token::EoS);
 
-        match previus_line {
-            // lex line starts from 0.
-            0 => print!("{:04} ", lexer.current_line + 1),
+    // The end of the line ends it too, before the next line is lexed
+    let mut lexer = lex_source("\"a {b\nc");
+    lexer.expect(segment("a "));
+    lexer.expect(Token::Identifier("b".to_string()));
 
-            _ if previus_line == lexer.current_line + 1 => print!("  |  "),
-            _ => print!("{:04} ", lexer.current_line + 1),
+    let error = lexer
+        .advance()
+        .expect_err("interpolation should be unterminated");
+    assert!(matches!(
+        error.error,
+        LexicalErrorType::UnterminatedInterpolation
+    ));
+    assert_eq!(error.span.line, 1);
+    lexer.expect(Token::Identifier("c".to_string()));
+    lexer.expect(Token::EoS);
+
+    // A comment inside the interpolation still ends with its line
+    let mut lexer = lex_source("\"a {b -- comment\nc");
+    lexer.expect(segment("a "));
+    lexer.expect(Token::Identifier("b".to_string()));
+
+    let error = lexer
+        .advance()
+        .expect_err("interpolation should be unterminated");
+    assert!(matches!(
+        error.error,
+        LexicalErrorType::UnterminatedInterpolation
+    ));
+    assert_eq!(error.span.line, 1);
+    lexer.expect(Token::Identifier("c".to_string()));
+    lexer.expect(Token::EoS);
+
+    // Reported at the braces, the rest of the string being lexed as usual
+    let mut lexer = lex_source("'a {} b'");
+    let error = lexer.advance().expect_err("interpolation should be empty");
+    assert!(matches!(error.error, LexicalErrorType::EmptyInterpolation));
+    assert_eq!(
+        error.span,
+        Span {
+            start: 3,
+            end: 5,
+            line: 1,
+            column: 4
         }
+    );
+    lexer.expect(string(" b"));
+    lexer.expect(Token::EoS);
+}
 
-        previus_line = lexer.current_line + 1;
+#[test]
+fn test_lexer_unicode() {
+    let mut lexer = lex_source("\"Torta de maçã\" '\\u{48}\\u{e9}\\xC3\\xA9 \\u{1F600}' ç");
+
+    lexer.expect(Token::String {
+        value: "Torta de maçã".to_string(),
+    });
+    lexer.expect(Token::String {
+        value: "Héé 😀".to_string(),
+    });
+
+    // Columns count characters, not bytes
+    let (_, span) = lexer
+        .advance()
+        .unwrap_or_else(|error| (Token::EoS, error.span));
+    assert_eq!((span.column, span.start, span.end), (50, 51, 53));
+
+    for source in [&b"a \xff"[..], b"\xc3(", b"\xe0\x80\x80", b"'\xff'"] {
+        let mut lexer = Lexer::new(source);
+        let error = loop {
+            match lexer.advance() {
+                Ok((Token::EoS, _)) => panic!("{source:?} should fail to lex"),
+                Ok(_) => continue,
+                Err(error) => break error,
+            }
+        };
+
+        assert!(
+            matches!(error.error, LexicalErrorType::InvalidUtf8),
+            "{source:?}: {error:?}"
+        );
+    }
+}
+
+#[cfg(feature = "unicode_identifiers")]
+#[test]
+fn test_lexer_unicode_identifiers() {
+    let mut lexer = lex_source("maçã _ação1 Δx\n名前");
+
+    for name in ["maçã", "_ação1", "Δx", "名前"] {
+        lexer.expect(Token::Identifier(name.to_string()));
     }
+    lexer.expect(Token::EoS);
+
+    let error = lex_source("a→")
+        .advance()
+        .expect_err("arrow is not part of identifiers");
+    assert!(matches!(
+        error.error,
+        LexicalErrorType::UnexpectedToken { token: '→' }
+    ));
+}
+
+#[test]
+fn test_lexer_spans() {
+    let mut lexer = lex_source("mut int a = 10\n  -- comment\n\t'multi\nline' 0xFF");
+    let expected = [
+        (Token::Mut, (1, 1, 0, 3)),
+        (Token::Identifier("int".to_string()), (1, 5, 4, 7)),
+        (Token::Identifier("a".to_string()), (1, 9, 8, 9)),
+        (Token::Assign, (1, 11, 10, 11)),
+        (Token::Int { value: 10 }, (1, 13, 12, 14)),
+        (
+            Token::String {
+                value: "multi\nline".to_string(),
+            },
+            (3, 2, 29, 41),
+        ),
+        (Token::Int { value: 0xFF }, (4, 7, 42, 46)),
+        (Token::EoS, (4, 11, 46, 46)),
+    ];
+
+    for (expected_token, (line, column, start, end)) in expected {
+        let (token, span) = lexer.advance().expect("source should lex");
+
+        assert_eq!(token, expected_token);
+        assert_eq!(
+            span,
+            Span {
+                start,
+                end,
+                line,
+                column
+            },
+            "{token:?}"
+        );
+    }
+}
+
+#[test]
+fn test_lexer_long_trivia() {
+    // Skipped in a loop, so it doesn't grow the stack
+    let source = "-- comment\n  --[[ block ]]\n\n".repeat(100_000) + "x";
+    let mut lexer = lex_source(&source);
+
+    lexer.expect(Token::Identifier("x".to_string()));
+    lexer.expect(Token::EoS);
+}
+
+#[test]
+fn test_lexer_long_bracket_spans() {
+    let mut lexer = lex_source("--[[\n\n]] x [=[\n\r\nline\n]]]=] y\n--[==[]==]z");
+    let expected = [
+        (Token::Identifier("x".to_string()), (3, 4, 9, 10)),
+        (
+            Token::String {
+                value: "\r\nline\n]]".to_string(),
+            },
+            (3, 6, 11, 27),
+        ),
+        (Token::Identifier("y".to_string()), (6, 7, 28, 29)),
+        (Token::Identifier("z".to_string()), (7, 11, 40, 41)),
+        (Token::EoS, (7, 12, 41, 41)),
+    ];
+
+    for (expected_token, (line, column, start, end)) in expected {
+        let (token, span) = lexer.advance().expect("source should lex");
+
+        assert_eq!(token, expected_token);
+        assert_eq!(
+            span,
+            Span {
+                start,
+                end,
+                line,
+                column
+            },
+            "{token:?}"
+        );
+    }
+}
+
+#[test]
+fn test_lexer_io_error() {
+    struct FailingReader;
+
+    impl std::io::Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("disk on fire"))
+        }
+    }
+
+    let mut lexer = Lexer::new(FailingReader);
+    let error = lexer
+        .advance()
+        .expect_err("failing reader should fail to lex");
+
+    assert!(matches!(error.error, LexicalErrorType::Io { .. }));
+    lexer.expect(Token::EoS);
 }
@@ -1,4 +1,40 @@
 use macros::ConvertToTokenRule;
+use std::fmt;
+
+/// Location of a piece of source code.
+/// `start` and `end` are byte offsets (`end` is exclusive), while `line` and
+/// `column` are 1-based and point to `start`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: i32,
+    pub column: i32,
+}
+
+impl Span {
+    /// Span covering from the start of this one to the end of the other
+    pub fn to(self, end: Span) -> Span {
+        Span {
+            end: end.end,
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+impl Span {
+    /// Span pointing to the start of the given line
+    pub fn at_line(line: i32) -> Span {
+        Span {
+            line,
+            ..Default::default()
+        }
+    }
+}
+
+/// A token paired with where it was found in the source code
+pub type Spanned = (Token, Span);
 
 #[derive(Debug, Clone, ConvertToTokenRule)]
 pub enum Token {
@@ -23,6 +59,7 @@ pub enum Token {
     Until,
     Require,
     Break,
+    Print,
 
     // Operations
     //   +     -   *    /    %    ^    #
@@ -60,7 +97,7 @@ pub enum Token {
     SqurR,
     DoubColon,
 
-    //      ;        :       ,      .    <>     ..     ->
+    //      ;        :       ,      .    <>     ..     ->      ?
     SemiColon,
     Colon,
     Comma,
@@ -68,13 +105,29 @@ pub enum Token {
     Concat,
     Dots,
     Arrow,
+    Question,
 
     // Data types (refers to to their actual value no keywords)
-    Int { value: i32 },
-    Float { value: f64 },
-    String { value: String },
-    Bool { value: bool },
-    Byte { value: u8 },
+    Int {
+        value: i32,
+    },
+    Float {
+        value: f64,
+    },
+    String {
+        value: String,
+    },
+    /// Text of a string up to an interpolation, followed by the tokens of the
+    /// interpolated expression and then the rest of the string
+    Interpolation {
+        value: String,
+    },
+    Bool {
+        value: bool,
+    },
+    Byte {
+        value: u8,
+    },
 
     Identifier(String),
 
@@ -90,6 +143,9 @@ impl PartialEq for Token {
             (Self::String { value: l_value }, Self::String { value: r_value }) => {
                 l_value == r_value
             }
+            (Self::Interpolation { value: l_value }, Self::Interpolation { value: r_value }) => {
+                l_value == r_value
+            }
             (Self::Bool { value: l_value }, Self::Bool { value: r_value }) => l_value == r_value,
             (Self::Byte { value: l_value }, Self::Byte { value: r_value }) => l_value == r_value,
             (Self::Identifier(l0), Self::Identifier(r0)) => l0 == r0,
@@ -98,6 +154,81 @@ impl PartialEq for Token {
     }
 }
 
+/// Describes the token as it's written in the source code, for error messages
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Token::And => "and",
+            Token::Do => "do",
+            Token::Then => "then",
+            Token::If => "if",
+            Token::Else => "else",
+            Token::ElseIf => "elseif",
+            Token::End => "end",
+            Token::For => "for",
+            Token::In => "in",
+            Token::Function => "function",
+            Token::Mut => "mut",
+            Token::Nil => "nil",
+            Token::Not => "not",
+            Token::Or => "or",
+            Token::While => "while",
+            Token::Repeat => "repeat",
+            Token::Return => "return",
+            Token::Until => "until",
+            Token::Require => "require",
+            Token::Break => "break",
+            Token::Print => "print",
+            Token::Add => "+",
+            Token::Sub => "-",
+            Token::Mul => "*",
+            Token::Div => "/",
+            Token::Mod => "%",
+            Token::Pow => "^",
+            Token::Len => "#",
+            Token::BitAnd => "&",
+            Token::BitOr => "|",
+            Token::BitNot => "~",
+            Token::ShiftL => "<<",
+            Token::ShiftR => ">>",
+            Token::Idiv => "//",
+            Token::Equal => "==",
+            Token::NotEq => "~=",
+            Token::LesEq => "<=",
+            Token::GreEq => ">=",
+            Token::Less => "<",
+            Token::Greater => ">",
+            Token::Assign => "=",
+            Token::ParL => "(",
+            Token::ParR => ")",
+            Token::CurlyL => "{",
+            Token::CurlyR => "}",
+            Token::SqurL => "[",
+            Token::SqurR => "]",
+            Token::DoubColon => "::",
+            Token::SemiColon => ";",
+            Token::Colon => ":",
+            Token::Comma => ",",
+            Token::Dot => ".",
+            Token::Concat => "<>",
+            Token::Dots => "..",
+            Token::Arrow => "->",
+            Token::Question => "?",
+
+            Token::Int { value } => return write!(f, "number `{value}`"),
+            Token::Float { value } => return write!(f, "number `{value:?}`"),
+            Token::String { value } => return write!(f, "string {value:?}"),
+            Token::Interpolation { value } => return write!(f, "string {value:?}"),
+            Token::Bool { value } => return write!(f, "`{value}`"),
+            Token::Byte { value } => return write!(f, "byte `{value}b`"),
+            Token::Identifier(name) => return write!(f, "identifier `{name}`"),
+            Token::EoS => return write!(f, "end of file"),
+        };
+
+        write!(f, "`{text}`")
+    }
+}
+
 // REVIEW - Consider convert to a impl of Token
 #[repr(u8)]
 #[derive(Debug)]
@@ -122,6 +253,7 @@ pub enum TokenRule {
     Until,
     Require,
     Break,
+    Print,
     Add,
     Sub,
     Mul,
@@ -156,9 +288,11 @@ pub enum TokenRule {
     Concat,
     Dots,
     Arrow,
+    Question,
     Int,
     Float,
     String,
+    Interpolation,
     Bool,
     Byte,
 
@@ -166,60 +300,121 @@ pub enum TokenRule {
     EoS,
 }
 
+/// Expected tokens for `tests/lang/test_lexer.poke`, in order
 #[cfg(test)]
-pub const TOKENS_MOCK: [&Token; 52] = [
-    // Testing operator and comments
-    &Token::Sub,
-    &Token::Add,
-    &Token::Mul,
-    &Token::Mod,
-    &Token::Pow,
-    &Token::Len,
-    &Token::BitAnd,
-    &Token::BitOr,
-    &Token::ParL,
-    &Token::ParR,
-    &Token::CurlyL,
-    &Token::CurlyR,
-    &Token::SqurL,
-    &Token::SqurR,
-    &Token::SemiColon,
-    &Token::Comma,
-    &Token::Colon,
-    &Token::Colon,
-    &Token::Div,
-    &Token::Div,
-    &Token::Equal,
-    &Token::Equal,
-    &Token::NotEq,
-    &Token::NotEq,
-    &Token::LesEq,
-    &Token::ShiftL,
-    &Token::Less,
-    &Token::Greater,
-    &Token::GreEq,
-    &Token::ShiftR,
-    &Token::ShiftR,
-    &Token::Arrow,
-    // Testing keywords
-    &Token::Mut,
-    &Token::Require,
-    &Token::And,
-    &Token::Break,
-    &Token::Do,
-    &Token::Else,
-    &Token::ElseIf,
-    &Token::End,
-    &Token::For,
-    &Token::Function,
-    &Token::If,
-    &Token::In,
-    &Token::Nil,
-    &Token::Not,
-    &Token::Or,
-    &Token::Repeat,
-    &Token::Return,
-    &Token::Then,
-    &Token::Until,
-    &Token::While,
-];
+pub fn tokens_mock() -> Vec<Token> {
+    let identifier = |name: &str| Token::Identifier(name.to_string());
+    let string = |value: &str| Token::String {
+        value: value.to_string(),
+    };
+
+    vec![
+        // Testing operator and comments
+        Token::Sub,
+        Token::Add,
+        Token::Mul,
+        Token::Mod,
+        Token::Pow,
+        Token::Len,
+        Token::BitAnd,
+        Token::BitOr,
+        Token::ParL,
+        Token::ParR,
+        Token::CurlyL,
+        Token::CurlyR,
+        Token::SqurL,
+        Token::SqurR,
+        Token::SemiColon,
+        Token::Comma,
+        Token::Colon,
+        Token::DoubColon,
+        Token::Div,
+        Token::Idiv,
+        Token::Assign,
+        Token::Equal,
+        Token::BitNot,
+        Token::NotEq,
+        Token::LesEq,
+        Token::ShiftL,
+        Token::Concat,
+        Token::Greater,
+        Token::GreEq,
+        Token::ShiftR,
+        Token::Arrow,
+        Token::Dot,
+        Token::Dots,
+        // Testing keywords
+        identifier("int"),
+        identifier("float"),
+        identifier("string"),
+        identifier("bool"),
+        Token::Mut,
+        Token::Require,
+        Token::And,
+        Token::Break,
+        Token::Do,
+        Token::Else,
+        Token::ElseIf,
+        Token::End,
+        Token::Bool { value: false },
+        Token::For,
+        Token::Function,
+        identifier("goto"),
+        Token::If,
+        Token::In,
+        Token::Nil,
+        Token::Not,
+        Token::Or,
+        Token::Repeat,
+        Token::Return,
+        Token::Then,
+        Token::Bool { value: true },
+        Token::Until,
+        Token::While,
+        identifier("Teste"),
+        // Numbers
+        Token::Int { value: 10 },
+        Token::Int { value: 2 },
+        Token::Int { value: 3 },
+        Token::Int { value: 4 },
+        Token::Int { value: 5 },
+        Token::Float { value: 0.1 },
+        Token::Float { value: 2.5 },
+        Token::Float { value: 6.8 },
+        Token::Float { value: 5.1e+8 },
+        Token::Float { value: 0.2 },
+        Token::Float { value: 0.468e2 },
+        Token::Float { value: 0.6e-28 },
+        Token::Byte { value: 2 },
+        Token::Byte { value: 45 },
+        Token::Int { value: 0xFF },
+        Token::Int { value: 0o17 },
+        Token::Int { value: 0b1010 },
+        Token::Int { value: 0xFFFF },
+        Token::Byte { value: 0xFF },
+        Token::Byte { value: 0b1 },
+        Token::Byte { value: 0 },
+        Token::Bool { value: true },
+        Token::Bool { value: false },
+        // Strings
+        string("sex123"),
+        string("hi"),
+        string("simple"),
+        string("string"),
+        string("\n"),
+        string("\t"),
+        string("\x07"),
+        string("\x08"),
+        string("\x0c"),
+        string("\x0b"),
+        string("\t"),
+        string("\\"),
+        string("\""),
+        string("'"),
+        string("A"),
+        string("\0"),
+        // Long strings
+        string("raw \\n string"),
+        string("with ]] and ]=] inside"),
+    ]
+}
@@ -1,15 +1,328 @@
+use crate::chunk::Chunk;
+use macros::AllVariants;
 use std::{
+    cell::RefCell,
+    collections::HashMap,
     error::Error,
     fmt::{self},
+    hash::{Hash, Hasher},
+    mem,
+    rc::Rc,
 };
 
+/// Types a variable can be declared with, e.g. `mut int a = 10`
+#[repr(u8)]
+#[derive(AllVariants, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataType {
+    Int,
+    Float,
+    Byte,
+    Bool,
+    String,
+    Table,
+}
+
+impl DataType {
+    /// Set in the `CheckType` operand of types written with a `?`, which
+    /// also accept `nil`
+    pub const NULLABLE: u8 = 0x80;
+
+    /// Type names aren't keywords, they are read from identifiers
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "int" => Some(DataType::Int),
+            "float" => Some(DataType::Float),
+            "byte" => Some(DataType::Byte),
+            "bool" => Some(DataType::Bool),
+            "string" => Some(DataType::String),
+            "table" => Some(DataType::Table),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DataType::Int => "int",
+            DataType::Float => "float",
+            DataType::Byte => "byte",
+            DataType::Bool => "bool",
+            DataType::String => "string",
+            DataType::Table => "table",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
 // TODO - Finish value types
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum ValueType {
     Float(f64),
     Int(i32),
     Byte(u8),
+    Bool(bool),
     Nil,
+    Object(Obj),
+    /// Variable given to a `mut` parameter, read and written through by the callee
+    Reference(Place),
+}
+
+/// Where a referenced variable lives
+#[derive(Debug, Clone)]
+pub enum Place {
+    Global(String),
+    /// A local, shared the same way closures capture it
+    Upvalue(Rc<RefCell<ObjUpvalue>>),
+}
+
+impl PartialEq for Place {
+    /// References are equal when they refer to the same variable
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Place::Global(left), Place::Global(right)) => left == right,
+            (Place::Upvalue(left), Place::Upvalue(right)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+}
+
+/// Values living in the heap, shared by reference counting
+/// LINK - https://craftinginterpreters.com/strings.html#values-and-objects
+#[derive(Debug, Clone)]
+pub enum Obj {
+    String(Rc<ObjString>),
+    Function(Rc<ObjFunction>),
+    Closure(Rc<ObjClosure>),
+    /// Tables are mutable, every variable holding one sees its changes
+    Table(Rc<RefCell<ObjTable>>),
+}
+
+impl PartialEq for Obj {
+    /// Objects are only equal to themselves. Strings are interned, so the same
+    /// text is always the same object.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Obj::String(left), Obj::String(right)) => Rc::ptr_eq(left, right),
+            (Obj::Function(left), Obj::Function(right)) => Rc::ptr_eq(left, right),
+            (Obj::Closure(left), Obj::Closure(right)) => Rc::ptr_eq(left, right),
+            (Obj::Table(left), Obj::Table(right)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+}
+
+impl Obj {
+    /// Address of the object, which identifies it
+    fn address(&self) -> usize {
+        match self {
+            Obj::String(string) => Rc::as_ptr(string) as usize,
+            Obj::Function(function) => Rc::as_ptr(function) as usize,
+            Obj::Closure(closure) => Rc::as_ptr(closure) as usize,
+            Obj::Table(table) => Rc::as_ptr(table) as *const () as usize,
+        }
+    }
+}
+
+/// Immutable text, interned by the virtual machine so each text is only
+/// stored once
+/// LINK - https://craftinginterpreters.com/hash-tables.html#string-interning
+#[derive(Hash, PartialEq, Eq)]
+pub struct ObjString {
+    pub chars: String,
+}
+
+impl ObjString {
+    pub fn new(chars: impl Into<String>) -> Self {
+        ObjString {
+            chars: chars.into(),
+        }
+    }
+}
+
+impl fmt::Debug for ObjString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.chars)
+    }
+}
+
+/// A parameter of a function, `mut` ones taking their argument by reference
+/// and nullable ones, like `int? a`, also taking `nil`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Param {
+    pub data_type: DataType,
+    pub nullable: bool,
+    pub mutable: bool,
+}
+
+/// Type of the values a function returns, nullable ones like `-> int?` also
+/// allowing it to return `nil`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReturnType {
+    pub data_type: DataType,
+    pub nullable: bool,
+}
+
+/// A compiled function, owning its bytecode
+/// LINK - https://craftinginterpreters.com/calls-and-functions.html#function-objects
+pub struct ObjFunction {
+    pub name: String,
+    /// Checked when it's called
+    pub params: Vec<Param>,
+    /// `None` for functions returning `void`
+    pub return_type: Option<ReturnType>,
+    pub chunk: Chunk,
+}
+
+impl ObjFunction {
+    pub fn new(name: &str) -> Self {
+        ObjFunction {
+            name: name.to_string(),
+            params: Vec::new(),
+            return_type: None,
+            chunk: Chunk::new(),
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.params.len()
+    }
+}
+
+impl fmt::Debug for ObjFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.name)
+    }
+}
+
+/// A function together with the variables it captured, the only kind of
+/// function that is called at runtime
+/// LINK - https://craftinginterpreters.com/closures.html#closure-objects
+pub struct ObjClosure {
+    pub function: Rc<ObjFunction>,
+    pub upvalues: Vec<Rc<RefCell<ObjUpvalue>>>,
+}
+
+impl fmt::Debug for ObjClosure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.function.name)
+    }
+}
+
+/// Key of a table entry. Like in Lua, numbers with an integer value are the
+/// same key whatever their type, and floats are only kept for the rest.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableKey {
+    Int(i32),
+    Float(f64),
+    Bool(bool),
+    /// Strings are interned, so they are compared like any other object
+    Object(Obj),
+}
+
+// NOTE - `NaN` can't be a key, so every float is equal to itself
+impl Eq for TableKey {}
+
+impl Hash for TableKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+
+        match self {
+            TableKey::Int(value) => value.hash(state),
+            TableKey::Float(value) => value.to_bits().hash(state),
+            TableKey::Bool(value) => value.hash(state),
+            TableKey::Object(object) => object.address().hash(state),
+        }
+    }
+}
+
+impl From<TableKey> for ValueType {
+    fn from(key: TableKey) -> Self {
+        match key {
+            TableKey::Int(value) => ValueType::Int(value),
+            TableKey::Float(value) => ValueType::Float(value),
+            TableKey::Bool(value) => ValueType::Bool(value),
+            TableKey::Object(object) => ValueType::Object(object),
+        }
+    }
+}
+
+/// Associative array, the only data structure of the language
+/// LINK - https://www.lua.org/manual/5.4/manual.html#2.1
+#[derive(Default)]
+pub struct ObjTable {
+    /// Entries in the order they were added, so iterating over them is
+    /// deterministic. Removed entries are kept with a `nil` value, so a loop
+    /// can remove the entries it visits without losing its place.
+    entries: Vec<(TableKey, ValueType)>,
+    /// Position of each key in the entries
+    positions: HashMap<TableKey, usize>,
+}
+
+impl ObjTable {
+    /// Value of the key, `nil` when it's not in the table
+    pub fn get(&self, key: &TableKey) -> ValueType {
+        self.positions
+            .get(key)
+            .map_or(ValueType::Nil, |&position| self.entries[position].1.clone())
+    }
+
+    /// Assigning `nil` removes the key from the table
+    pub fn set(&mut self, key: TableKey, value: ValueType) {
+        match self.positions.get(&key) {
+            Some(&position) => self.entries[position].1 = value,
+            None if value == ValueType::Nil => {}
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    /// Like in Lua, an int key whose value isn't `nil` while the next one is,
+    /// or `0` if the key `1` is `nil`. It's the length of a sequence, a table
+    /// with the keys from `1` to some `n`, when it has no other border.
+    /// LINK - https://www.lua.org/manual/5.4/manual.html#3.4.7
+    pub fn border(&self) -> i32 {
+        let mut border = 0;
+        while border < i32::MAX && self.get(&TableKey::Int(border + 1)) != ValueType::Nil {
+            border += 1;
+        }
+
+        border
+    }
+
+    /// Entry after the given key, or the first one without a key. `None` when
+    /// there are no more entries, or the key isn't in the table.
+    pub fn next(&self, key: Option<&TableKey>) -> Option<(TableKey, ValueType)> {
+        let start = match key {
+            Some(key) => self.positions.get(key)? + 1,
+            None => 0,
+        };
+
+        self.entries[start..]
+            .iter()
+            .find(|(_, value)| *value != ValueType::Nil)
+            .cloned()
+    }
+}
+
+// NOTE - A table may contain itself, so its entries aren't printed
+impl fmt::Debug for ObjTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<table>")
+    }
+}
+
+/// A captured local, which stays in the stack while it's in scope and is
+/// moved into the upvalue once its scope ends
+/// LINK - https://craftinginterpreters.com/closures.html#upvalues
+#[derive(Debug)]
+pub enum ObjUpvalue {
+    /// Index of the local in the stack
+    Open(usize),
+    Closed(ValueType),
 }
 
 impl ValueType {
@@ -20,6 +333,77 @@ impl ValueType {
             _ => Err("Can't negate non numeric or unsigned values".into()),
         }
     }
+
+    /// Like in Lua, only `nil` and `false` are falsey
+    pub fn is_falsey(&self) -> bool {
+        matches!(self, ValueType::Nil | ValueType::Bool(false))
+    }
+
+    /// Name of the value's type, as written in the source code
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ValueType::Float(_) => "float",
+            ValueType::Int(_) => "int",
+            ValueType::Byte(_) => "byte",
+            ValueType::Bool(_) => "bool",
+            ValueType::Nil => "nil",
+            ValueType::Object(Obj::String(_)) => "string",
+            ValueType::Object(Obj::Function(_) | Obj::Closure(_)) => "function",
+            ValueType::Object(Obj::Table(_)) => "table",
+            ValueType::Reference(_) => "reference",
+        }
+    }
+
+    /// Type of a variable able to store the value, if it can be written
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            ValueType::Float(_) => Some(DataType::Float),
+            ValueType::Int(_) => Some(DataType::Int),
+            ValueType::Byte(_) => Some(DataType::Byte),
+            ValueType::Bool(_) => Some(DataType::Bool),
+            ValueType::Object(Obj::String(_)) => Some(DataType::String),
+            ValueType::Object(Obj::Table(_)) => Some(DataType::Table),
+            _ => None,
+        }
+    }
+
+    /// Key of a table entry holding the value, `nil` and `NaN` can't be keys
+    pub fn table_key(&self) -> Option<TableKey> {
+        match *self {
+            ValueType::Int(value) => Some(TableKey::Int(value)),
+            ValueType::Byte(value) => Some(TableKey::Int(value as i32)),
+            ValueType::Float(value) if value.is_nan() => None,
+            ValueType::Float(value) => {
+                let integer = value as i32;
+
+                // NOTE - Also turns `-0.0` into `0`
+                if integer as f64 == value {
+                    Some(TableKey::Int(integer))
+                } else {
+                    Some(TableKey::Float(value))
+                }
+            }
+            ValueType::Bool(value) => Some(TableKey::Bool(value)),
+            ValueType::Object(ref object) => Some(TableKey::Object(object.clone())),
+            ValueType::Nil | ValueType::Reference(_) => None,
+        }
+    }
+
+    /// Converts the value so it can be stored in a variable of the given type.
+    /// Ints are promoted to floats, any other mismatch fails.
+    pub fn coerce(self, data_type: DataType) -> Option<Self> {
+        match (&self, data_type) {
+            (ValueType::Int(value), DataType::Float) => Some(ValueType::Float(*value as f64)),
+            (ValueType::Float(_), DataType::Float)
+            | (ValueType::Int(_), DataType::Int)
+            | (ValueType::Byte(_), DataType::Byte)
+            | (ValueType::Bool(_), DataType::Bool)
+            | (ValueType::Object(Obj::String(_)), DataType::String)
+            | (ValueType::Object(Obj::Table(_)), DataType::Table) => Some(self),
+
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for ValueType {
@@ -28,7 +412,16 @@ impl fmt::Display for ValueType {
             ValueType::Float(value) => write!(f, "{value:?}"),
             ValueType::Int(value) => write!(f, "{value}"),
             ValueType::Byte(value) => write!(f, "{value}"),
+            ValueType::Bool(value) => write!(f, "{value}"),
             ValueType::Nil => write!(f, "nil"),
+            ValueType::Object(Obj::String(string)) => write!(f, "{}", string.chars),
+            ValueType::Object(Obj::Function(function)) => write!(f, "<fn {}>", function.name),
+            ValueType::Object(Obj::Closure(closure)) => {
+                write!(f, "<fn {}>", closure.function.name)
+            }
+            ValueType::Object(Obj::Table(_)) => write!(f, "<table>"),
+            ValueType::Reference(Place::Global(name)) => write!(f, "<ref {name}>"),
+            ValueType::Reference(Place::Upvalue(_)) => write!(f, "<ref>"),
         }
     }
 }
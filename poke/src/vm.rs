@@ -1,41 +1,208 @@
 use crate::{
     chunk::{ByteCode, Chunk},
-    value::ValueType,
+    parser::tokens::Span,
+    value::{DataType, Obj, ObjClosure, ObjString, ObjTable, ObjUpvalue, Place, ValueType},
 };
 
 #[cfg(feature = "debug_trace_execution")]
 use crate::debug::disassemble_instruction;
 
-use std::ops;
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt,
+    io::{self, Write},
+    mem,
+    rc::Rc,
+};
+
+/// Default limit of nested calls, after which the stack overflows
+pub const FRAMES_MAX: usize = 256;
+
+#[derive(Debug)]
+pub enum RuntimeErrorType {
+    StackUnderflow,
+    UnknownInstruction {
+        byte: u8,
+    },
+    TypeError {
+        message: String,
+    },
+    MismatchedTypes {
+        expected: DataType,
+        found: &'static str,
+    },
+    UndefinedVariable {
+        name: String,
+    },
+    ZeroForStep,
+    NotCallable {
+        found: &'static str,
+    },
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    StackOverflow,
+    ExpectedReference {
+        name: String,
+        position: usize,
+    },
+    ArithmeticOverflow {
+        data_type: DataType,
+    },
+    DivisionByZero,
+    NotIndexable {
+        found: &'static str,
+    },
+    InvalidKey {
+        found: &'static str,
+    },
+    Io {
+        error: io::Error,
+    },
+}
+
+impl fmt::Display for RuntimeErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeErrorType::StackUnderflow => write!(f, "stack underflow"),
+            RuntimeErrorType::UnknownInstruction { byte } => {
+                write!(f, "unknown instruction {byte:#04x}")
+            }
+            RuntimeErrorType::TypeError { message } => write!(f, "{message}"),
+            RuntimeErrorType::MismatchedTypes { expected, found } => {
+                write!(f, "mismatched types: expected {expected}, found {found}")
+            }
+            RuntimeErrorType::UndefinedVariable { name } => {
+                write!(f, "undefined variable `{name}`")
+            }
+            RuntimeErrorType::ZeroForStep => write!(f, "'for' step is zero"),
+            RuntimeErrorType::NotCallable { found } => {
+                write!(f, "cannot call a value of type {found}")
+            }
+            RuntimeErrorType::ArityMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "function `{name}` takes {expected} arguments but {found} were given"
+            ),
+            RuntimeErrorType::StackOverflow => write!(f, "stack overflow"),
+            RuntimeErrorType::ExpectedReference { name, position } => write!(
+                f,
+                "argument {position} of function `{name}` must be a mutable variable"
+            ),
+            RuntimeErrorType::ArithmeticOverflow { data_type } => {
+                write!(
+                    f,
+                    "arithmetic overflow, the result doesn't fit in {data_type}"
+                )
+            }
+            RuntimeErrorType::DivisionByZero => write!(f, "attempt to divide by zero"),
+            RuntimeErrorType::NotIndexable { found } => {
+                write!(f, "cannot index a value of type {found}")
+            }
+            RuntimeErrorType::InvalidKey { found } => write!(f, "table key can't be {found}"),
+            RuntimeErrorType::Io { error } => write!(f, "failed to write output: {error}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub error: RuntimeErrorType,
+    /// Source location of the instruction that failed
+    pub span: Span,
+}
 
 #[derive(Debug)]
 pub enum InterpretError {
-    CompilerError,
-    RuntimeError,
+    RuntimeError(RuntimeError),
 }
 
 pub type InterpretResult = Result<(), InterpretError>;
 
+/// A function being run
+/// LINK - https://craftinginterpreters.com/calls-and-functions.html#call-frames
+struct CallFrame {
+    /// `None` for the script
+    closure: Option<Rc<ObjClosure>>,
+    /// Where the function returns to in the caller, saved while it calls other functions
+    ip: usize,
+    /// Stack index of the frame's first slot, the called function itself
+    slots: usize,
+}
+
 pub struct VirtualMachine<'a> {
-    chunk: &'a Chunk,
+    script: &'a Chunk,
     // REVIEW - might be a slow solution. In the book a raw pointer is used wich is usafe rust
-    /// Holds the index of the current instruction within the bytecode array
+    /// Holds the index of the current instruction within the current function's bytecode array
     ip: usize,
+    /// Functions being run, the current one being the last
+    frames: Vec<CallFrame>,
+    /// Amount of frames allowed before overflowing
+    max_depth: usize,
 
     stack: Vec<ValueType>,
+    /// Upvalues of the locals still in the stack, ordered by their stack index
+    open_upvalues: Vec<Rc<RefCell<ObjUpvalue>>>,
+    globals: HashMap<String, ValueType>,
+    /// Every string created so far, so equal strings are the same object
+    strings: HashSet<Rc<ObjString>>,
+    /// Where `print` writes to
+    output: Box<dyn Write + 'a>,
 }
 
 impl<'a> VirtualMachine<'a> {
     pub fn new(chunk: &'a Chunk) -> Self {
+        VirtualMachine::with_output(chunk, io::stdout())
+    }
+
+    pub fn with_output(chunk: &'a Chunk, output: impl Write + 'a) -> Self {
         VirtualMachine {
-            chunk,
+            script: chunk,
             ip: 0,
+            frames: vec![CallFrame {
+                closure: None,
+                ip: 0,
+                slots: 0,
+            }],
+            max_depth: FRAMES_MAX,
             stack: Vec::new(),
+            open_upvalues: Vec::new(),
+            globals: HashMap::new(),
+            strings: HashSet::new(),
+            output: Box::new(output),
+        }
+    }
+
+    /// Limits the amount of nested calls
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+
+        self
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames
+            .last()
+            .expect("the script's frame is never popped")
+    }
+
+    /// Chunk of the function being run
+    fn chunk(&self) -> &Chunk {
+        match &self.frame().closure {
+            Some(closure) => &closure.function.chunk,
+            None => self.script,
         }
     }
 
     fn peek_current_instruction(&self) -> &u8 {
-        self.chunk
+        self.chunk()
             .code
             .get(self.ip)
             .expect("Failed to peek into chunk instructions")
@@ -48,154 +215,2193 @@ impl<'a> VirtualMachine<'a> {
         val
     }
 
-    // FIXME - binary operation automatically conver type to float
-    fn binary_op(&mut self, op: fn(f64, f64) -> f64) -> Option<InterpretResult> {
-        let left = match self.stack.pop()? {
-            ValueType::Float(value) => value,
-            ValueType::Int(value) => value as f64,
-            ValueType::Byte(value) => value as f64,
-            ValueType::Nil => todo!(),
+    fn pop(&mut self) -> Result<ValueType, RuntimeErrorType> {
+        self.stack.pop().ok_or(RuntimeErrorType::StackUnderflow)
+    }
+
+    fn pop_n(&mut self) -> Result<(), RuntimeErrorType> {
+        let count = self.advance_ip(1) as usize;
+        let new_len = self
+            .stack
+            .len()
+            .checked_sub(count)
+            .ok_or(RuntimeErrorType::StackUnderflow)?;
+
+        self.stack.truncate(new_len);
+
+        Ok(())
+    }
+
+    fn read_constant_long(&mut self) -> ValueType {
+        let constant_index = self.advance_ip(1) as usize
+            | (self.advance_ip(1) as usize) << 8
+            | (self.advance_ip(1) as usize) << 16;
+
+        self.chunk().constants[constant_index].clone()
+    }
+
+    /// String constants are interned when they're loaded, since each chunk
+    /// has its own copy
+    fn push_constant(&mut self, constant: ValueType) {
+        let constant = match constant {
+            ValueType::Object(Obj::String(string)) => {
+                ValueType::Object(Obj::String(self.intern(string)))
+            }
+            constant => constant,
+        };
+
+        self.stack.push(constant);
+    }
+
+    /// The string with the same text that was created first
+    fn intern(&mut self, string: Rc<ObjString>) -> Rc<ObjString> {
+        if let Some(interned) = self.strings.get(&string) {
+            return Rc::clone(interned);
+        }
+
+        self.strings.insert(Rc::clone(&string));
+
+        string
+    }
+
+    fn read_short(&mut self) -> usize {
+        self.advance_ip(1) as usize | (self.advance_ip(1) as usize) << 8
+    }
+
+    fn jump_if_false(&mut self) -> Result<(), RuntimeErrorType> {
+        let jump = self.read_short();
+        let condition = self.stack.last().ok_or(RuntimeErrorType::StackUnderflow)?;
+
+        if condition.is_falsey() {
+            self.ip += jump;
+        }
+
+        Ok(())
+    }
+
+    /// Index of the first of the given amount of values on top of the stack
+    fn stack_top(&self, count: usize) -> Result<usize, RuntimeErrorType> {
+        self.stack
+            .len()
+            .checked_sub(count)
+            .ok_or(RuntimeErrorType::StackUnderflow)
+    }
+
+    /// Prepares a numeric `for` loop, whose start, limit and step are on top of
+    /// the stack. Like in Lua 5.4, it counts with ints when both the start and
+    /// the step are integers, otherwise with floats.
+    /// LINK - https://www.lua.org/manual/5.4/manual.html#3.3.5
+    fn for_prep(&mut self) -> Result<(), RuntimeErrorType> {
+        let jump = self.read_short();
+        let state_start = self.stack_top(3)?;
+        let (start, limit, step) = (
+            &self.stack[state_start],
+            &self.stack[state_start + 1],
+            &self.stack[state_start + 2],
+        );
+
+        let (state, runs) = match (as_integer(start), as_integer(step)) {
+            (Some(start), Some(step)) => {
+                if step == 0 {
+                    return Err(RuntimeErrorType::ZeroForStep);
+                }
+
+                // NOTE - A float limit is rounded towards the loop's direction
+                let limit = match (as_integer(limit), for_number(limit, "limit")?) {
+                    (Some(limit), _) => limit,
+                    (None, limit) if step > 0 => limit.floor() as i32,
+                    (None, limit) => limit.ceil() as i32,
+                };
+
+                let runs = if step > 0 {
+                    start <= limit
+                } else {
+                    start >= limit
+                };
+                let state = [start, limit, step].map(ValueType::Int);
+
+                (state, runs)
+            }
+            _ => {
+                let start = for_number(start, "initial")?;
+                let limit = for_number(limit, "limit")?;
+                let step = for_number(step, "step")?;
+
+                if step == 0.0 {
+                    return Err(RuntimeErrorType::ZeroForStep);
+                }
+
+                let runs = if step > 0.0 {
+                    start <= limit
+                } else {
+                    start >= limit
+                };
+                let state = [start, limit, step].map(ValueType::Float);
+
+                (state, runs)
+            }
         };
 
-        let right = match self.stack.pop()? {
-            ValueType::Float(value) => value,
-            ValueType::Int(value) => value as f64,
-            ValueType::Byte(value) => value as f64,
-            ValueType::Nil => todo!(),
+        self.stack.truncate(state_start);
+        self.stack.extend(state);
+
+        if !runs {
+            self.ip += jump;
+
+            return Ok(());
+        }
+
+        // The loop variable, a copy of the counter
+        self.stack.push(self.stack[state_start].clone());
+
+        Ok(())
+    }
+
+    fn for_loop(&mut self) -> Result<(), RuntimeErrorType> {
+        let jump = self.read_short();
+        let state_start = self.stack_top(3)?;
+
+        let next = match &self.stack[state_start..] {
+            [ValueType::Int(counter), ValueType::Int(limit), ValueType::Int(step)] => {
+                // NOTE - Computed as i64 so the counter can't overflow past the limit
+                let next = *counter as i64 + *step as i64;
+                let runs = if *step > 0 {
+                    next <= *limit as i64
+                } else {
+                    next >= *limit as i64
+                };
+
+                runs.then_some(ValueType::Int(next as i32))
+            }
+            [ValueType::Float(counter), ValueType::Float(limit), ValueType::Float(step)] => {
+                let next = counter + step;
+                let runs = if *step > 0.0 {
+                    next <= *limit
+                } else {
+                    next >= *limit
+                };
+
+                runs.then_some(ValueType::Float(next))
+            }
+            _ => {
+                return Err(RuntimeErrorType::TypeError {
+                    message: "invalid 'for' loop state".to_string(),
+                })
+            }
         };
 
-        let op_result = op(right, left);
+        if let Some(next) = next {
+            self.stack[state_start] = next.clone();
+            self.stack.push(next);
+            self.ip -= jump;
+        }
+
+        Ok(())
+    }
+
+    /// Calls the iterator of a generic `for` loop, with its state and control
+    /// value on top of the stack. Tables aren't called, the key after the
+    /// control value is pushed instead.
+    fn for_call(&mut self) -> Result<(), RuntimeErrorType> {
+        let state_start = self.stack_top(3)?;
+
+        if let ValueType::Object(Obj::Table(table)) = &self.stack[state_start] {
+            let control = self.stack[state_start + 2].table_key();
+            let key = table
+                .borrow()
+                .next(control.as_ref())
+                .map_or(ValueType::Nil, |(key, _)| key.into());
 
-        self.stack.push(ValueType::Float(op_result));
+            self.stack.push(key);
 
-        Some(Ok(()))
+            return Ok(());
+        }
+
+        self.stack.extend_from_within(state_start..);
+        self.call_value(2)
     }
 
-    pub fn run_interpreter(&mut self) -> InterpretResult {
-        #[cfg(feature = "debug_trace_execution")]
+    /// Ends the generic `for` loop when its iterator returns `nil`, otherwise
+    /// the returned value becomes the control value and the first loop variable
+    fn for_call_loop(&mut self) -> Result<(), RuntimeErrorType> {
+        let count = self.advance_ip(1) as usize;
+        let jump = self.read_short();
+        let value = self.pop()?;
+
+        if value == ValueType::Nil {
+            self.ip += jump;
+
+            return Ok(());
+        }
+
+        let control = self.stack_top(1)?;
+        self.stack[control] = value.clone();
+
+        // Iterators return a single value, the other variables are `nil`,
+        // except the value of the key when iterating over a table
+        let mut values = vec![value.clone()];
+        if let (ValueType::Object(Obj::Table(table)), Some(key)) =
+            (&self.stack[control - 2], value.table_key())
         {
-            println!("\n==== Stack Trace ====");
+            values.push(table.borrow().get(&key));
         }
 
-        let mut offset: usize;
-        let mut text: String;
+        values.resize(count, ValueType::Nil);
+        self.stack.extend(values);
 
-        loop {
-            // prints each instruction right before executing it.
-            #[cfg(feature = "debug_trace_execution")]
-            {
-                for value in &self.stack {
-                    println!("STACK [{:?}]", value);
-                }
+        Ok(())
+    }
 
-                println!("-");
-                (text, offset) = disassemble_instruction(self.chunk, self.ip);
-                println!("{:04} {}", offset, text);
+    fn read_identifier(&mut self) -> String {
+        let identifier_index = self.read_short();
+
+        self.chunk().identifiers[identifier_index].clone()
+    }
+
+    fn define_global(&mut self) -> Result<(), RuntimeErrorType> {
+        let name = self.read_identifier();
+        let value = self.pop()?;
+
+        self.globals.insert(name, value);
+
+        Ok(())
+    }
+
+    fn get_global(&mut self) -> Result<(), RuntimeErrorType> {
+        let name = self.read_identifier();
+        let value = self
+            .globals
+            .get(&name)
+            .cloned()
+            .ok_or_else(|| RuntimeErrorType::UndefinedVariable { name: name.clone() })?;
+
+        self.stack.push(value);
+
+        Ok(())
+    }
+
+    fn set_global(&mut self) -> Result<(), RuntimeErrorType> {
+        let name = self.read_identifier();
+        let value = self.pop()?;
+
+        let global = self
+            .globals
+            .get_mut(&name)
+            .ok_or(RuntimeErrorType::UndefinedVariable { name })?;
+
+        // NOTE - Functions assigning a global declared after them couldn't be
+        // type checked when compiled, so the global keeps the type it has
+        *global = match global.data_type() {
+            Some(data_type) => {
+                let found = value.type_name();
+                value
+                    .coerce(data_type)
+                    .ok_or(RuntimeErrorType::MismatchedTypes {
+                        expected: data_type,
+                        found,
+                    })?
             }
+            None => value,
+        };
 
-            let instruction: u8 = self.advance_ip(1);
-            let operation = ByteCode::all_variants().get(instruction as usize);
-            if operation.is_none() {
-                panic!("(vm) instruction not found: {}", instruction)
+        Ok(())
+    }
+
+    /// Pushes the value of a local, reading through it if it's a reference
+    fn get_local(&mut self) -> Result<(), RuntimeErrorType> {
+        let slot = self.frame().slots + self.advance_ip(1) as usize;
+        let value = match self.stack.get(slot) {
+            Some(ValueType::Reference(place)) => self.read_place(place)?,
+            Some(value) => value.clone(),
+            None => return Err(RuntimeErrorType::StackUnderflow),
+        };
+
+        self.stack.push(value);
+
+        Ok(())
+    }
+
+    /// Stores the value on top of the stack into a local, writing through it
+    /// if it's a reference
+    fn set_local(&mut self) -> Result<(), RuntimeErrorType> {
+        let slot = self.frame().slots + self.advance_ip(1) as usize;
+        let value = self.pop()?;
+
+        match self.stack.get(slot) {
+            Some(ValueType::Reference(place)) => self.write_place(place.clone(), value),
+            Some(_) => {
+                self.stack[slot] = value;
+
+                Ok(())
             }
+            None => Err(RuntimeErrorType::StackUnderflow),
+        }
+    }
 
-            return match operation.unwrap() {
-                ByteCode::Constant => {
-                    let constant: ValueType = self.chunk.constants[self.advance_ip(1) as usize];
-                    self.stack.push(constant);
+    fn ref_local(&mut self) -> Result<(), RuntimeErrorType> {
+        let slot = self.frame().slots + self.advance_ip(1) as usize;
+        let reference = match self.stack.get(slot) {
+            // NOTE - `mut` parameters pass on the variable they refer to
+            Some(ValueType::Reference(place)) => ValueType::Reference(place.clone()),
+            Some(_) => ValueType::Reference(Place::Upvalue(self.capture_upvalue(slot))),
+            None => return Err(RuntimeErrorType::StackUnderflow),
+        };
 
-                    continue;
-                }
-                ByteCode::Negate => {
-                    if let Some(value) = self.stack.pop() {
-                        self.stack.push(
-                            value
-                                .negate()
-                                .unwrap_or_else(|err| panic!("failed to negate value: {:?}", err)),
-                        );
-                    }
+        self.stack.push(reference);
 
-                    continue;
-                }
+        Ok(())
+    }
 
-                ByteCode::Add => {
-                    let _ = self
-                        .binary_op(ops::Add::add)
-                        .unwrap_or(Err(InterpretError::RuntimeError));
+    fn ref_global(&mut self) -> Result<(), RuntimeErrorType> {
+        let name = self.read_identifier();
 
-                    continue;
-                }
-                ByteCode::Subtract => {
-                    let _ = self
-                        .binary_op(ops::Sub::sub)
-                        .unwrap_or(Err(InterpretError::RuntimeError));
+        if !self.globals.contains_key(&name) {
+            return Err(RuntimeErrorType::UndefinedVariable { name });
+        }
 
-                    continue;
-                }
-                ByteCode::Multiply => {
-                    let _ = self
-                        .binary_op(ops::Mul::mul)
-                        .unwrap_or(Err(InterpretError::RuntimeError));
+        self.stack.push(ValueType::Reference(Place::Global(name)));
 
-                    continue;
-                }
-                ByteCode::Divide => {
-                    let _ = self
-                        .binary_op(ops::Div::div)
-                        .unwrap_or(Err(InterpretError::RuntimeError));
+        Ok(())
+    }
 
-                    continue;
-                }
+    fn read_place(&self, place: &Place) -> Result<ValueType, RuntimeErrorType> {
+        match place {
+            Place::Global(name) => self
+                .globals
+                .get(name)
+                .cloned()
+                .ok_or_else(|| RuntimeErrorType::UndefinedVariable { name: name.clone() }),
+            Place::Upvalue(upvalue) => self.read_upvalue(upvalue),
+        }
+    }
 
-                ByteCode::Return => {
-                    return Ok(());
-                }
+    fn write_place(&mut self, place: Place, value: ValueType) -> Result<(), RuntimeErrorType> {
+        match place {
+            Place::Global(name) => match self.globals.get_mut(&name) {
+                Some(global) => *global = value,
+                None => return Err(RuntimeErrorType::UndefinedVariable { name }),
+            },
+            Place::Upvalue(upvalue) => self.write_upvalue(&upvalue, value)?,
+        }
+
+        Ok(())
+    }
+
+    fn read_upvalue(&self, upvalue: &RefCell<ObjUpvalue>) -> Result<ValueType, RuntimeErrorType> {
+        match &*upvalue.borrow() {
+            ObjUpvalue::Open(index) => self
+                .stack
+                .get(*index)
+                .cloned()
+                .ok_or(RuntimeErrorType::StackUnderflow),
+            ObjUpvalue::Closed(value) => Ok(value.clone()),
+        }
+    }
+
+    fn write_upvalue(
+        &mut self,
+        upvalue: &RefCell<ObjUpvalue>,
+        value: ValueType,
+    ) -> Result<(), RuntimeErrorType> {
+        match &mut *upvalue.borrow_mut() {
+            ObjUpvalue::Open(index) => {
+                *self
+                    .stack
+                    .get_mut(*index)
+                    .ok_or(RuntimeErrorType::StackUnderflow)? = value
+            }
+            ObjUpvalue::Closed(closed) => *closed = value,
+        }
 
-                _ => Err(InterpretError::CompilerError),
+        Ok(())
+    }
+
+    /// Upvalue of the current closure
+    fn upvalue(&self, index: usize) -> Rc<RefCell<ObjUpvalue>> {
+        let closure = self
+            .frame()
+            .closure
+            .as_ref()
+            .expect("only closures have upvalues");
+
+        Rc::clone(&closure.upvalues[index])
+    }
+
+    fn read_upvalue_operand(&mut self) -> Rc<RefCell<ObjUpvalue>> {
+        let index = self.advance_ip(1) as usize;
+
+        self.upvalue(index)
+    }
+
+    /// Pushes the value of a captured variable, reading through it if it's a reference
+    fn get_upvalue(&mut self) -> Result<(), RuntimeErrorType> {
+        let upvalue = self.read_upvalue_operand();
+        let value = match self.read_upvalue(&upvalue)? {
+            ValueType::Reference(place) => self.read_place(&place)?,
+            value => value,
+        };
+
+        self.stack.push(value);
+
+        Ok(())
+    }
+
+    /// Stores the value on top of the stack into a captured variable, writing
+    /// through it if it's a reference
+    fn set_upvalue(&mut self) -> Result<(), RuntimeErrorType> {
+        let upvalue = self.read_upvalue_operand();
+        let value = self.pop()?;
+
+        match self.read_upvalue(&upvalue)? {
+            ValueType::Reference(place) => self.write_place(place, value),
+            _ => self.write_upvalue(&upvalue, value),
+        }
+    }
+
+    fn ref_upvalue(&mut self) -> Result<(), RuntimeErrorType> {
+        let upvalue = self.read_upvalue_operand();
+        let reference = match self.read_upvalue(&upvalue)? {
+            // NOTE - `mut` parameters pass on the variable they refer to
+            ValueType::Reference(place) => ValueType::Reference(place),
+            _ => ValueType::Reference(Place::Upvalue(upvalue)),
+        };
+
+        self.stack.push(reference);
+
+        Ok(())
+    }
+
+    /// Wraps the function on top of the stack into a closure, capturing the
+    /// locals of the current function or sharing the upvalues of its closure
+    fn closure(&mut self) -> Result<(), RuntimeErrorType> {
+        let count = self.advance_ip(1) as usize;
+        let function = match self.pop()? {
+            ValueType::Object(Obj::Function(function)) => function,
+            value => {
+                return Err(RuntimeErrorType::NotCallable {
+                    found: value.type_name(),
+                })
+            }
+        };
+
+        let mut upvalues = Vec::with_capacity(count);
+        for _ in 0..count {
+            let is_local = self.advance_ip(1) == 1;
+            let index = self.advance_ip(1) as usize;
+
+            let upvalue = if is_local {
+                self.capture_upvalue(self.frame().slots + index)
+            } else {
+                self.upvalue(index)
             };
+
+            upvalues.push(upvalue);
         }
+
+        self.stack
+            .push(ValueType::Object(Obj::Closure(Rc::new(ObjClosure {
+                function,
+                upvalues,
+            }))));
+
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::debug::_disassemble_chunk;
+    /// Upvalue of the local at the given stack index, shared by every closure capturing it
+    fn capture_upvalue(&mut self, index: usize) -> Rc<RefCell<ObjUpvalue>> {
+        let position = self
+            .open_upvalues
+            .partition_point(|upvalue| open_index(upvalue) < index);
 
-    #[test]
-    fn test_binary_unary_operations() {
-        let mut chunk = Chunk::new();
+        if let Some(upvalue) = self.open_upvalues.get(position) {
+            if open_index(upvalue) == index {
+                return Rc::clone(upvalue);
+            }
+        }
 
-        chunk.write_constant(ValueType::Float(6.2), 128);
-        chunk.write_chunk(ByteCode::Negate as u8, 2);
+        let upvalue = Rc::new(RefCell::new(ObjUpvalue::Open(index)));
+        self.open_upvalues.insert(position, Rc::clone(&upvalue));
 
-        chunk.write_constant(ValueType::Float(1.0), 132);
-        chunk.write_chunk(ByteCode::Add as u8, 132);
+        upvalue
+    }
 
-        chunk.write_constant(ValueType::Float(5.0), 132);
-        chunk.write_constant(ValueType::Float(1.0), 132);
-        chunk.write_chunk(ByteCode::Subtract as u8, 132);
+    /// Discards the local on top of the stack, which was captured
+    fn close_upvalue(&mut self) -> Result<(), RuntimeErrorType> {
+        self.close_upvalues(self.stack_top(1)?);
+        self.pop()?;
 
-        chunk.write_constant(ValueType::Float(4.2), 132);
-        chunk.write_constant(ValueType::Float(3.0), 132);
-        chunk.write_chunk(ByteCode::Multiply as u8, 132);
+        Ok(())
+    }
 
-        chunk.write_constant(ValueType::Float(4.0), 132);
-        chunk.write_constant(ValueType::Float(0.5), 132);
-        chunk.write_chunk(ByteCode::Divide as u8, 132);
+    /// Moves the locals from the given stack index upwards into their upvalues,
+    /// since they are about to be discarded
+    fn close_upvalues(&mut self, from: usize) {
+        while let Some(upvalue) = self.open_upvalues.last() {
+            let index = open_index(upvalue);
+            if index < from {
+                return;
+            }
 
-        chunk.write_chunk(ByteCode::Return as u8, 123);
+            let value = self.stack.get(index).cloned().unwrap_or(ValueType::Nil);
+            *upvalue.borrow_mut() = ObjUpvalue::Closed(value);
+            self.open_upvalues.pop();
+        }
+    }
 
-        #[cfg(feature = "debug_trace_execution")]
-        _disassemble_chunk(&chunk, "test chunk");
+    /// Makes sure the value on top of the stack fits the type it's stored as
+    fn check_type(&mut self) -> Result<(), RuntimeErrorType> {
+        let type_byte = self.advance_ip(1);
+        let data_type = *DataType::all_variants()
+            .get((type_byte & !DataType::NULLABLE) as usize)
+            .ok_or(RuntimeErrorType::UnknownInstruction { byte: type_byte })?;
 
-        let mut vm = VirtualMachine::new(&chunk);
-        vm.run_interpreter()
-            .unwrap_or_else(|error| panic!("VM failed: {:?}", error));
+        let value = self.pop()?;
+        if type_byte & DataType::NULLABLE != 0 && matches!(value, ValueType::Nil) {
+            self.stack.push(value);
 
-        assert_eq!(vm.stack.pop(), Some(ValueType::Float(8.0))); // Divide
-        assert_eq!(vm.stack.pop(), Some(ValueType::Float(12.600000000000001))); // Multiply
-        assert_eq!(vm.stack.pop(), Some(ValueType::Float(4.0))); // Subtract
-        assert_eq!(vm.stack.pop(), Some(ValueType::Float(-5.2))); // Add & Negate
+            return Ok(());
+        }
+
+        let found = value.type_name();
+        let coerced = value
+            .coerce(data_type)
+            .ok_or(RuntimeErrorType::MismatchedTypes {
+                expected: data_type,
+                found,
+            })?;
+
+        self.stack.push(coerced);
+
+        Ok(())
+    }
+
+    /// Calls the value below the given amount of arguments on top of the stack.
+    /// The arguments become the first locals of the called function, `mut`
+    /// parameters taking references instead of values.
+    fn call_value(&mut self, argument_count: usize) -> Result<(), RuntimeErrorType> {
+        let slots = self.stack_top(argument_count + 1)?;
+
+        let closure = match &self.stack[slots] {
+            ValueType::Object(Obj::Closure(closure)) => Rc::clone(closure),
+            value => {
+                return Err(RuntimeErrorType::NotCallable {
+                    found: value.type_name(),
+                })
+            }
+        };
+
+        let function = &closure.function;
+        if argument_count != function.arity() {
+            return Err(RuntimeErrorType::ArityMismatch {
+                name: function.name.clone(),
+                expected: function.arity(),
+                found: argument_count,
+            });
+        }
+
+        for (position, param) in function.params.iter().enumerate() {
+            let slot = slots + 1 + position;
+
+            // NOTE - The parser already checked the type of the referenced variable
+            if param.mutable {
+                if !matches!(self.stack[slot], ValueType::Reference(_)) {
+                    return Err(RuntimeErrorType::ExpectedReference {
+                        name: function.name.clone(),
+                        position: position + 1,
+                    });
+                }
+
+                continue;
+            }
+
+            let argument = mem::replace(&mut self.stack[slot], ValueType::Nil);
+            if param.nullable && matches!(argument, ValueType::Nil) {
+                continue;
+            }
+
+            let found = argument.type_name();
+
+            self.stack[slot] =
+                argument
+                    .coerce(param.data_type)
+                    .ok_or(RuntimeErrorType::MismatchedTypes {
+                        expected: param.data_type,
+                        found,
+                    })?;
+        }
+
+        if self.frames.len() >= self.max_depth {
+            return Err(RuntimeErrorType::StackOverflow);
+        }
+
+        // Where the caller resumes after the call
+        if let Some(caller) = self.frames.last_mut() {
+            caller.ip = self.ip;
+        }
+
+        self.frames.push(CallFrame {
+            closure: Some(closure),
+            ip: 0,
+            slots,
+        });
+        self.ip = 0;
+
+        Ok(())
+    }
+
+    /// Discards the frame of the current function, leaving its returned value
+    /// in place of the called function. The value was already checked against
+    /// the return type of the function.
+    fn return_from_function(&mut self) -> Result<(), RuntimeErrorType> {
+        let value = self.pop()?;
+        let frame = self.frames.pop().expect("only functions return");
+
+        self.close_upvalues(frame.slots);
+        self.stack.truncate(frame.slots);
+        self.stack.push(value);
+        self.ip = self.frame().ip;
+
+        Ok(())
+    }
+
+    fn print(&mut self) -> Result<(), RuntimeErrorType> {
+        let value = self.pop()?;
+
+        writeln!(self.output, "{value}").map_err(|error| RuntimeErrorType::Io { error })
+    }
+
+    fn negate(&mut self) -> Result<(), RuntimeErrorType> {
+        let value = self.pop()?;
+
+        // NOTE - The smallest int has no positive counterpart
+        if value == ValueType::Int(i32::MIN) {
+            return Err(RuntimeErrorType::ArithmeticOverflow {
+                data_type: DataType::Int,
+            });
+        }
+
+        let negated = value
+            .negate()
+            .map_err(|error| RuntimeErrorType::TypeError {
+                message: error.to_string(),
+            })?;
+
+        self.stack.push(negated);
+
+        Ok(())
+    }
+
+    /// Pops the right and left operands, pushing the result of the operation
+    fn binary_op(&mut self, arithmetic: Arithmetic) -> Result<(), RuntimeErrorType> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+
+        self.stack.push(arithmetic.apply(&left, &right)?);
+
+        Ok(())
+    }
+
+    fn bitwise_op(&mut self, bitwise: Bitwise) -> Result<(), RuntimeErrorType> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+
+        self.stack.push(bitwise.apply(&left, &right)?);
+
+        Ok(())
+    }
+
+    fn bit_not(&mut self) -> Result<(), RuntimeErrorType> {
+        let flipped = match self.pop()? {
+            ValueType::Byte(value) => ValueType::Byte(!value),
+            value => ValueType::Int(!bitwise_integer(&value)?),
+        };
+
+        self.stack.push(flipped);
+
+        Ok(())
+    }
+
+    /// Pops two strings or numbers, pushing a new string with both texts
+    fn concat(&mut self) -> Result<(), RuntimeErrorType> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+
+        for operand in [&left, &right] {
+            if !is_string(operand) && !is_number(operand) {
+                return Err(RuntimeErrorType::TypeError {
+                    message: format!("attempt to concatenate a {} value", operand.type_name()),
+                });
+            }
+        }
+
+        let string = self.intern(Rc::new(ObjString::new(format!("{left}{right}"))));
+        self.stack.push(ValueType::Object(Obj::String(string)));
+
+        Ok(())
+    }
+
+    fn stringify(&mut self) -> Result<(), RuntimeErrorType> {
+        let string = match self.pop()? {
+            ValueType::Object(Obj::String(string)) => string,
+            value => self.intern(Rc::new(ObjString::new(value.to_string()))),
+        };
+        self.stack.push(ValueType::Object(Obj::String(string)));
+
+        Ok(())
+    }
+
+    fn len(&mut self) -> Result<(), RuntimeErrorType> {
+        let length = match self.pop()? {
+            ValueType::Object(Obj::String(string)) => string.chars.len(),
+            ValueType::Object(Obj::Table(table)) => table.borrow().border() as usize,
+            value => {
+                return Err(RuntimeErrorType::TypeError {
+                    message: format!("attempt to get length of a {} value", value.type_name()),
+                })
+            }
+        };
+
+        let length = i32::try_from(length).map_err(|_| RuntimeErrorType::ArithmeticOverflow {
+            data_type: DataType::Int,
+        })?;
+        self.stack.push(ValueType::Int(length));
+
+        Ok(())
+    }
+
+    fn new_table(&mut self) -> Result<(), RuntimeErrorType> {
+        let table = Rc::new(RefCell::new(ObjTable::default()));
+        self.stack.push(ValueType::Object(Obj::Table(table)));
+
+        Ok(())
+    }
+
+    /// Adds a field of a table constructor to the table below it
+    fn init_field(&mut self) -> Result<(), RuntimeErrorType> {
+        let value = self.pop()?;
+        let key = self.pop()?;
+        let table = self.stack.last().ok_or(RuntimeErrorType::StackUnderflow)?;
+
+        store_in_table(table, key, value)
+    }
+
+    fn get_index(&mut self) -> Result<(), RuntimeErrorType> {
+        let key = self.pop()?;
+        let value = match self.pop()? {
+            // NOTE - Like in Lua, reading a key that can't be in a table gives `nil`
+            ValueType::Object(Obj::Table(table)) => key
+                .table_key()
+                .map_or(ValueType::Nil, |key| table.borrow().get(&key)),
+            value => {
+                return Err(RuntimeErrorType::NotIndexable {
+                    found: value.type_name(),
+                })
+            }
+        };
+
+        self.stack.push(value);
+
+        Ok(())
+    }
+
+    fn set_index(&mut self) -> Result<(), RuntimeErrorType> {
+        let value = self.pop()?;
+        let key = self.pop()?;
+        let table = self.pop()?;
+
+        store_in_table(&table, key, value.clone())?;
+        self.stack.push(value);
+
+        Ok(())
+    }
+
+    fn not(&mut self) -> Result<(), RuntimeErrorType> {
+        let value = self.pop()?;
+        self.stack.push(ValueType::Bool(value.is_falsey()));
+
+        Ok(())
+    }
+
+    fn equal(&mut self) -> Result<(), RuntimeErrorType> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+
+        let equal = match compare_numbers(&left, &right) {
+            Some(ordering) => ordering == Ordering::Equal,
+            None => left == right,
+        };
+        self.stack.push(ValueType::Bool(equal));
+
+        Ok(())
+    }
+
+    /// Pops two numbers, pushing whether the first compares to the second as expected
+    fn comparison(&mut self, expected: Ordering) -> Result<(), RuntimeErrorType> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+
+        if !is_number(&left) || !is_number(&right) {
+            return Err(RuntimeErrorType::TypeError {
+                message: format!(
+                    "attempt to compare {} with {}",
+                    left.type_name(),
+                    right.type_name()
+                ),
+            });
+        }
+
+        let ordering = compare_numbers(&left, &right);
+        self.stack.push(ValueType::Bool(ordering == Some(expected)));
+
+        Ok(())
+    }
+
+    fn runtime_error(&self, error: RuntimeErrorType, instruction_index: usize) -> InterpretError {
+        InterpretError::RuntimeError(RuntimeError {
+            error,
+            span: self.chunk().spans[instruction_index],
+        })
+    }
+
+    pub fn run_interpreter(&mut self) -> InterpretResult {
+        #[cfg(feature = "debug_trace_execution")]
+        {
+            println!("\n==== Stack Trace ====");
+        }
+
+        #[cfg(feature = "debug_trace_execution")]
+        let mut offset: usize;
+        #[cfg(feature = "debug_trace_execution")]
+        let mut text: String;
+
+        loop {
+            // prints each instruction right before executing it.
+            #[cfg(feature = "debug_trace_execution")]
+            {
+                for value in &self.stack {
+                    println!("STACK [{:?}]", value);
+                }
+
+                println!("-");
+                (text, offset) = disassemble_instruction(self.chunk(), self.ip);
+                println!("{:04} {}", offset, text);
+            }
+
+            let instruction_index = self.ip;
+            let instruction: u8 = self.advance_ip(1);
+            let operation = ByteCode::all_variants().get(instruction as usize);
+
+            let result = match operation {
+                Some(ByteCode::Constant) => {
+                    let constant_index = self.advance_ip(1) as usize;
+                    let constant = self.chunk().constants[constant_index].clone();
+                    self.push_constant(constant);
+
+                    Ok(())
+                }
+                Some(ByteCode::ConstantLong) => {
+                    let constant = self.read_constant_long();
+                    self.push_constant(constant);
+
+                    Ok(())
+                }
+                Some(ByteCode::Nil) => {
+                    self.stack.push(ValueType::Nil);
+
+                    Ok(())
+                }
+                Some(ByteCode::True) => {
+                    self.stack.push(ValueType::Bool(true));
+
+                    Ok(())
+                }
+                Some(ByteCode::False) => {
+                    self.stack.push(ValueType::Bool(false));
+
+                    Ok(())
+                }
+                Some(ByteCode::Negate) => self.negate(),
+
+                Some(ByteCode::Add) => self.binary_op(Arithmetic::Add),
+                Some(ByteCode::Subtract) => self.binary_op(Arithmetic::Subtract),
+                Some(ByteCode::Multiply) => self.binary_op(Arithmetic::Multiply),
+                Some(ByteCode::Divide) => self.binary_op(Arithmetic::Divide),
+                Some(ByteCode::Modulo) => self.binary_op(Arithmetic::Modulo),
+                Some(ByteCode::Power) => self.binary_op(Arithmetic::Power),
+                Some(ByteCode::IntDivide) => self.binary_op(Arithmetic::IntDivide),
+                Some(ByteCode::BitAnd) => self.bitwise_op(Bitwise::And),
+                Some(ByteCode::BitOr) => self.bitwise_op(Bitwise::Or),
+                Some(ByteCode::BitXor) => self.bitwise_op(Bitwise::Xor),
+                Some(ByteCode::ShiftLeft) => self.bitwise_op(Bitwise::ShiftLeft),
+                Some(ByteCode::ShiftRight) => self.bitwise_op(Bitwise::ShiftRight),
+                Some(ByteCode::BitNot) => self.bit_not(),
+                Some(ByteCode::Concat) => self.concat(),
+                Some(ByteCode::ToString) => self.stringify(),
+                Some(ByteCode::Len) => self.len(),
+                Some(ByteCode::Equal) => self.equal(),
+                Some(ByteCode::Less) => self.comparison(Ordering::Less),
+                Some(ByteCode::Greater) => self.comparison(Ordering::Greater),
+                Some(ByteCode::Not) => self.not(),
+
+                Some(ByteCode::Print) => self.print(),
+                Some(ByteCode::Pop) => self.pop().map(|_| ()),
+                Some(ByteCode::PopN) => self.pop_n(),
+
+                Some(ByteCode::DefineGlobal) => self.define_global(),
+                Some(ByteCode::GetGlobal) => self.get_global(),
+                Some(ByteCode::SetGlobal) => self.set_global(),
+                Some(ByteCode::GetLocal) => self.get_local(),
+                Some(ByteCode::SetLocal) => self.set_local(),
+                Some(ByteCode::CheckType) => self.check_type(),
+                Some(ByteCode::NewTable) => self.new_table(),
+                Some(ByteCode::InitField) => self.init_field(),
+                Some(ByteCode::GetIndex) => self.get_index(),
+                Some(ByteCode::SetIndex) => self.set_index(),
+
+                Some(ByteCode::Jump) => {
+                    self.ip += self.read_short();
+
+                    Ok(())
+                }
+                Some(ByteCode::JumpIfFalse) => self.jump_if_false(),
+                Some(ByteCode::Loop) => {
+                    self.ip -= self.read_short();
+
+                    Ok(())
+                }
+                Some(ByteCode::ForPrep) => self.for_prep(),
+                Some(ByteCode::ForLoop) => self.for_loop(),
+                Some(ByteCode::ForCall) => self.for_call(),
+                Some(ByteCode::ForCallLoop) => self.for_call_loop(),
+
+                Some(ByteCode::Call) => {
+                    let argument_count = self.advance_ip(1) as usize;
+
+                    self.call_value(argument_count)
+                }
+                Some(ByteCode::RefLocal) => self.ref_local(),
+                Some(ByteCode::RefGlobal) => self.ref_global(),
+                Some(ByteCode::RefUpvalue) => self.ref_upvalue(),
+                Some(ByteCode::Closure) => self.closure(),
+                Some(ByteCode::GetUpvalue) => self.get_upvalue(),
+                Some(ByteCode::SetUpvalue) => self.set_upvalue(),
+                Some(ByteCode::CloseUpvalue) => self.close_upvalue(),
+                Some(ByteCode::Return) => {
+                    // NOTE - The script's frame is the last one
+                    if self.frames.len() == 1 {
+                        return Ok(());
+                    }
+
+                    self.return_from_function()
+                }
+
+                None => Err(RuntimeErrorType::UnknownInstruction { byte: instruction }),
+            };
+
+            if let Err(error) = result {
+                return Err(self.runtime_error(error, instruction_index));
+            }
+        }
+    }
+}
+
+/// Assigns the value to the key of the table, `nil` removing the key
+fn store_in_table(
+    table: &ValueType,
+    key: ValueType,
+    value: ValueType,
+) -> Result<(), RuntimeErrorType> {
+    let ValueType::Object(Obj::Table(table)) = table else {
+        return Err(RuntimeErrorType::NotIndexable {
+            found: table.type_name(),
+        });
+    };
+
+    let key = match key {
+        ValueType::Float(value) if value.is_nan() => {
+            return Err(RuntimeErrorType::InvalidKey { found: "NaN" })
+        }
+        key => key.table_key().ok_or(RuntimeErrorType::InvalidKey {
+            found: key.type_name(),
+        })?,
+    };
+
+    table.borrow_mut().set(key, value);
+
+    Ok(())
+}
+
+/// Operations between two numbers. Ints and bytes keep their type, trapping
+/// when the result doesn't fit in it, while floats only show up when one of
+/// the operands is a float. Bytes mixed with ints are promoted to ints.
+#[derive(Debug, Clone, Copy)]
+enum Arithmetic {
+    Add,
+    Subtract,
+    Multiply,
+    /// Ints and bytes are truncated towards zero, so `-7 / 2` is `-3`, while
+    /// `//` rounds towards negative infinity
+    Divide,
+    /// Like in Lua 5.4, the result has the sign of the divisor
+    /// LINK - https://www.lua.org/manual/5.4/manual.html#3.4.1
+    Modulo,
+    /// Always results in a float, like in Lua
+    Power,
+    /// Rounds the quotient towards minus infinity
+    IntDivide,
+}
+
+impl Arithmetic {
+    fn apply(self, left: &ValueType, right: &ValueType) -> Result<ValueType, RuntimeErrorType> {
+        let keeps_integers = !matches!(self, Arithmetic::Power);
+
+        match (left, right) {
+            (ValueType::Byte(left), ValueType::Byte(right)) if keeps_integers => {
+                let result = self.integer(*left as i32, *right as i32)?;
+
+                u8::try_from(result).map(ValueType::Byte).map_err(|_| {
+                    RuntimeErrorType::ArithmeticOverflow {
+                        data_type: DataType::Byte,
+                    }
+                })
+            }
+            (ValueType::Int(_) | ValueType::Byte(_), ValueType::Int(_) | ValueType::Byte(_))
+                if keeps_integers =>
+            {
+                let (Some(left), Some(right)) = (as_integer(left), as_integer(right)) else {
+                    unreachable!("ints and bytes are integers");
+                };
+
+                self.integer(left, right).map(ValueType::Int)
+            }
+            _ => {
+                let left = arithmetic_number(left)?;
+                let right = arithmetic_number(right)?;
+
+                Ok(ValueType::Float(self.float(left, right)))
+            }
+        }
+    }
+
+    fn integer(self, left: i32, right: i32) -> Result<i32, RuntimeErrorType> {
+        let result = match self {
+            Arithmetic::Add => left.checked_add(right),
+            Arithmetic::Subtract => left.checked_sub(right),
+            Arithmetic::Multiply => left.checked_mul(right),
+            Arithmetic::Divide | Arithmetic::Modulo | Arithmetic::IntDivide if right == 0 => {
+                return Err(RuntimeErrorType::DivisionByZero)
+            }
+            Arithmetic::Divide => left.checked_div(right),
+            // NOTE - The only remainder that overflows, although it's always zero
+            Arithmetic::Modulo if right == -1 => Some(0),
+            Arithmetic::Modulo => left.checked_rem(right).map(|remainder| {
+                if remainder != 0 && (remainder < 0) != (right < 0) {
+                    remainder + right
+                } else {
+                    remainder
+                }
+            }),
+            Arithmetic::IntDivide => left.checked_div(right).map(|quotient| {
+                if left % right != 0 && (left < 0) != (right < 0) {
+                    quotient - 1
+                } else {
+                    quotient
+                }
+            }),
+            Arithmetic::Power => unreachable!("powers are computed with floats"),
+        };
+
+        result.ok_or(RuntimeErrorType::ArithmeticOverflow {
+            data_type: DataType::Int,
+        })
+    }
+
+    fn float(self, left: f64, right: f64) -> f64 {
+        match self {
+            Arithmetic::Add => left + right,
+            Arithmetic::Subtract => left - right,
+            Arithmetic::Multiply => left * right,
+            Arithmetic::Divide => left / right,
+            Arithmetic::Modulo => {
+                let remainder = left % right;
+
+                if remainder != 0.0 && (remainder < 0.0) != (right < 0.0) {
+                    remainder + right
+                } else {
+                    remainder
+                }
+            }
+            Arithmetic::Power => left.powf(right),
+            Arithmetic::IntDivide => (left / right).floor(),
+        }
+    }
+}
+
+/// Operations on the bits of ints and bytes. Like in Lua 5.4, floats with an
+/// exact integer value are converted to ints, and shifts are logical, filling
+/// the vacant bits with zeros.
+/// LINK - https://www.lua.org/manual/5.4/manual.html#3.4.2
+#[derive(Debug, Clone, Copy)]
+enum Bitwise {
+    And,
+    Or,
+    Xor,
+    /// Negative displacements shift to the other direction
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl Bitwise {
+    fn apply(self, left: &ValueType, right: &ValueType) -> Result<ValueType, RuntimeErrorType> {
+        if let (ValueType::Byte(left), ValueType::Byte(right)) = (left, right) {
+            return Ok(ValueType::Byte(self.byte(*left, *right)));
+        }
+
+        let left = bitwise_integer(left)?;
+        let right = bitwise_integer(right)?;
+
+        Ok(ValueType::Int(self.integer(left, right)))
+    }
+
+    fn integer(self, left: i32, right: i32) -> i32 {
+        match self {
+            Bitwise::And => left & right,
+            Bitwise::Or => left | right,
+            Bitwise::Xor => left ^ right,
+            Bitwise::ShiftLeft => shift_left(left, right as i64),
+            Bitwise::ShiftRight => shift_left(left, -(right as i64)),
+        }
+    }
+
+    /// Bits shifted out of the byte are lost
+    fn byte(self, left: u8, right: u8) -> u8 {
+        match self {
+            Bitwise::And => left & right,
+            Bitwise::Or => left | right,
+            Bitwise::Xor => left ^ right,
+            Bitwise::ShiftLeft => left.checked_shl(right as u32).unwrap_or(0),
+            Bitwise::ShiftRight => left.checked_shr(right as u32).unwrap_or(0),
+        }
+    }
+}
+
+fn shift_left(value: i32, displacement: i64) -> i32 {
+    let bits = value as u32;
+    let shifted = match displacement {
+        0..=31 => bits << displacement,
+        -31..=-1 => bits >> -displacement,
+        _ => 0,
+    };
+
+    shifted as i32
+}
+
+/// Converts an operand of a bitwise operation to an int
+fn bitwise_integer(value: &ValueType) -> Result<i32, RuntimeErrorType> {
+    match value {
+        ValueType::Int(value) => Ok(*value),
+        ValueType::Byte(value) => Ok(*value as i32),
+        ValueType::Float(float) => {
+            let integer = *float as i32;
+
+            if integer as f64 != *float {
+                return Err(RuntimeErrorType::TypeError {
+                    message: format!("number {float:?} has no integer representation"),
+                });
+            }
+
+            Ok(integer)
+        }
+        value => Err(RuntimeErrorType::TypeError {
+            message: format!(
+                "attempt to perform bitwise operation on a {} value",
+                value.type_name()
+            ),
+        }),
+    }
+}
+
+fn arithmetic_number(value: &ValueType) -> Result<f64, RuntimeErrorType> {
+    as_float(value).ok_or_else(|| RuntimeErrorType::TypeError {
+        message: format!(
+            "attempt to perform arithmetic on a {} value",
+            value.type_name()
+        ),
+    })
+}
+
+fn as_float(value: &ValueType) -> Option<f64> {
+    match value {
+        ValueType::Float(value) => Some(*value),
+        ValueType::Int(value) => Some(*value as f64),
+        ValueType::Byte(value) => Some(*value as f64),
+        _ => None,
+    }
+}
+
+fn is_number(value: &ValueType) -> bool {
+    as_float(value).is_some()
+}
+
+fn is_string(value: &ValueType) -> bool {
+    matches!(value, ValueType::Object(Obj::String(_)))
+}
+
+/// Orders two numbers of any type, `None` if any of them isn't a number or is NaN.
+/// Integers are compared exactly, any of them fits in a float.
+fn compare_numbers(left: &ValueType, right: &ValueType) -> Option<Ordering> {
+    if let (Some(left), Some(right)) = (as_integer(left), as_integer(right)) {
+        return Some(left.cmp(&right));
+    }
+
+    as_float(left)?.partial_cmp(&as_float(right)?)
+}
+
+/// Stack index of an upvalue that wasn't closed yet
+fn open_index(upvalue: &RefCell<ObjUpvalue>) -> usize {
+    match *upvalue.borrow() {
+        ObjUpvalue::Open(index) => index,
+        ObjUpvalue::Closed(_) => unreachable!("closed upvalues are not kept open"),
+    }
+}
+
+/// Value of an int or a byte, bytes being promoted to ints
+fn as_integer(value: &ValueType) -> Option<i32> {
+    match value {
+        ValueType::Int(value) => Some(*value),
+        ValueType::Byte(value) => Some(*value as i32),
+        _ => None,
+    }
+}
+
+fn for_number(value: &ValueType, name: &str) -> Result<f64, RuntimeErrorType> {
+    match value {
+        ValueType::Float(value) => Ok(*value),
+        ValueType::Int(value) => Ok(*value as f64),
+        ValueType::Byte(value) => Ok(*value as f64),
+        _ => Err(RuntimeErrorType::TypeError {
+            message: format!("'for' {name} value must be a number"),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        debug::_disassemble_chunk,
+        parser::{parser::Parser, tokens::Span},
+    };
+
+    /// Compiles and runs the source code, returning what it printed
+    fn interpret(source: &str) -> Result<String, InterpretError> {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load(source.as_bytes())
+            .unwrap_or_else(|errors| panic!("failed to compile {source:?}: {errors:?}"));
+
+        let mut output = Vec::new();
+        VirtualMachine::with_output(&chunk, &mut output).run_interpreter()?;
+
+        Ok(String::from_utf8(output).expect("output should be valid UTF-8"))
+    }
+
+    #[test]
+    fn test_binary_unary_operations() {
+        let mut chunk = Chunk::new();
+
+        chunk.write_constant(ValueType::Float(6.2), Span::at_line(128));
+        chunk.write_chunk(ByteCode::Negate as u8, Span::at_line(2));
+
+        chunk.write_constant(ValueType::Float(1.0), Span::at_line(132));
+        chunk.write_chunk(ByteCode::Add as u8, Span::at_line(132));
+
+        chunk.write_constant(ValueType::Float(5.0), Span::at_line(132));
+        chunk.write_constant(ValueType::Float(1.0), Span::at_line(132));
+        chunk.write_chunk(ByteCode::Subtract as u8, Span::at_line(132));
+
+        chunk.write_constant(ValueType::Float(4.2), Span::at_line(132));
+        chunk.write_constant(ValueType::Float(3.0), Span::at_line(132));
+        chunk.write_chunk(ByteCode::Multiply as u8, Span::at_line(132));
+
+        chunk.write_constant(ValueType::Float(4.0), Span::at_line(132));
+        chunk.write_constant(ValueType::Float(0.5), Span::at_line(132));
+        chunk.write_chunk(ByteCode::Divide as u8, Span::at_line(132));
+
+        chunk.write_chunk(ByteCode::Return as u8, Span::at_line(123));
+
+        #[cfg(feature = "debug_trace_execution")]
+        _disassemble_chunk(&chunk, "test chunk");
+
+        let mut vm = VirtualMachine::new(&chunk);
+        vm.run_interpreter()
+            .unwrap_or_else(|error| panic!("VM failed: {:?}", error));
+
+        assert_eq!(vm.stack.pop(), Some(ValueType::Float(8.0))); // Divide
+        assert_eq!(vm.stack.pop(), Some(ValueType::Float(12.600000000000001))); // Multiply
+        assert_eq!(vm.stack.pop(), Some(ValueType::Float(4.0))); // Subtract
+        assert_eq!(vm.stack.pop(), Some(ValueType::Float(-5.2))); // Add & Negate
+    }
+
+    #[test]
+    fn test_runtime_error_span() {
+        let mut chunk = Chunk::new();
+
+        chunk.write_constant(ValueType::Nil, Span::at_line(3));
+        chunk.write_chunk(ByteCode::Negate as u8, Span::at_line(4));
+        chunk.write_chunk(ByteCode::Return as u8, Span::at_line(4));
+
+        let mut vm = VirtualMachine::new(&chunk);
+        match vm.run_interpreter() {
+            Err(InterpretError::RuntimeError(error)) => {
+                assert!(matches!(error.error, RuntimeErrorType::TypeError { .. }));
+                assert_eq!(error.span.line, 4);
+            }
+            result => panic!("expected runtime error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_statements() {
+        let output = interpret(
+            "print 1 + 2 * 3
+            do
+                print (1 + 2) * 3
+                4 - 1
+            end
+            ; print -2",
+        )
+        .expect("program should run");
+
+        assert_eq!(output, "7\n9\n-2\n");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let output = interpret(
+            "print 7 / 2
+            print -7 / 2
+            print 7 / -2
+            print -7 / -2
+            print -7 // 2
+            print 7 / 2.0
+            print 1 + 0.5
+            print 250b + 5b
+            print 2b * 3
+            print 1.0 / 0",
+        )
+        .expect("program should run");
+
+        assert_eq!(output, "3\n-3\n-3\n3\n-4\n3.5\n1.5\n255\n6\ninf\n");
+
+        let run_error = |source: &str| match interpret(source) {
+            Err(InterpretError::RuntimeError(error)) => error.error,
+            result => panic!("expected a runtime error, got {result:?}"),
+        };
+
+        assert!(matches!(
+            run_error("print 2147483647 + 1"),
+            RuntimeErrorType::ArithmeticOverflow {
+                data_type: DataType::Int
+            }
+        ));
+        assert!(matches!(
+            run_error("print -(-2147483647 - 1)"),
+            RuntimeErrorType::ArithmeticOverflow {
+                data_type: DataType::Int
+            }
+        ));
+        assert!(matches!(
+            run_error("print (-2147483647 - 1) / -1"),
+            RuntimeErrorType::ArithmeticOverflow {
+                data_type: DataType::Int
+            }
+        ));
+        assert!(matches!(
+            run_error("print 255b + 1b"),
+            RuntimeErrorType::ArithmeticOverflow {
+                data_type: DataType::Byte
+            }
+        ));
+        assert!(matches!(
+            run_error("print 0b - 1b"),
+            RuntimeErrorType::ArithmeticOverflow {
+                data_type: DataType::Byte
+            }
+        ));
+        assert!(matches!(
+            run_error("print 1 / 0"),
+            RuntimeErrorType::DivisionByZero
+        ));
+        assert!(matches!(
+            run_error("print 1b / 0b"),
+            RuntimeErrorType::DivisionByZero
+        ));
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let output = interpret(
+            "function f() end
+            print 1 == 1.0
+            print 2b == 2
+            print 1 ~= 2
+            print f == f
+            print f == 1
+            print 1 < 2.5
+            print 3 <= 3
+            print 2.5 > 3
+            print 3b >= 4
+            bool b = 1 < 2
+            print b
+            if 2 > 3 then print 1 else print 0 end",
+        )
+        .expect("program should run");
+
+        assert_eq!(
+            output,
+            "true\ntrue\ntrue\ntrue\nfalse\ntrue\ntrue\nfalse\nfalse\ntrue\n0\n"
+        );
+
+        let error =
+            interpret("function f() end\nprint 1 < f").expect_err("functions can't be ordered");
+        let InterpretError::RuntimeError(error) = error;
+        assert_eq!(
+            error.error.to_string(),
+            "attempt to compare int with function"
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        let output = interpret(
+            "print nil
+            print true
+            print not nil
+            print not 0
+            print not 1 == 2
+            mut bool b = false
+            b = not b
+            print b",
+        )
+        .expect("program should run");
+
+        assert_eq!(output, "nil\ntrue\ntrue\nfalse\nfalse\ntrue\n");
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        let output = interpret(
+            "mut int calls = 0
+            function touch(int value) -> int
+                calls = calls + 1
+                return value
+            end
+            print nil and touch(1)
+            print false or touch(2)
+            print 3 or touch(4)
+            print 5 and touch(6)
+            print nil or false
+            print 1 < 2 and 2 < 3 or touch(7)
+            print calls",
+        )
+        .expect("program should run");
+
+        // Only the right operands that decided the result were called
+        assert_eq!(output, "nil\n2\n3\n6\nfalse\ntrue\n2\n");
+    }
+
+    #[test]
+    fn test_modulo_power_int_divide() {
+        let output = interpret(
+            "print 7 % 3
+            print -7 % 3
+            print 7 % -3
+            print 5.5 % -2
+            print 7 // 2
+            print -7 // 2
+            print -7.5 // 2
+            print 7b // 2b
+            print 2 ^ 3 ^ 2
+            print -2 ^ 2
+            print 2 ^ -1
+            print 2 * 3 % 4",
+        )
+        .expect("program should run");
+
+        assert_eq!(
+            output,
+            "1\n2\n-2\n-0.5\n3\n-4\n-4.0\n3\n512.0\n-4.0\n0.5\n2\n"
+        );
+
+        let run_error = |source: &str| match interpret(source) {
+            Err(InterpretError::RuntimeError(error)) => error.error,
+            result => panic!("expected a runtime error, got {result:?}"),
+        };
+
+        assert!(matches!(
+            run_error("print 1 % 0"),
+            RuntimeErrorType::DivisionByZero
+        ));
+        assert!(matches!(
+            run_error("print 1 // 0"),
+            RuntimeErrorType::DivisionByZero
+        ));
+    }
+
+    #[test]
+    fn test_bitwise_operators() {
+        let output = interpret(
+            "print 6 & 3
+            print 6 | 3
+            print 6 ~ 3
+            print ~0
+            print ~0b
+            print 1 << 4
+            print -1 >> 28
+            print 1 << 32
+            print 16 << -2
+            print 255b << 1b
+            print 2.0 | 1
+            print 1 | 2 ~ 3 & 4 << 1",
+        )
+        .expect("program should run");
+
+        // The last one being 1 | (2 ~ (3 & (4 << 1)))
+        assert_eq!(output, "2\n7\n5\n-1\n255\n16\n15\n0\n4\n254\n3\n3\n");
+
+        let run_error = |source: &str| match interpret(source) {
+            Err(InterpretError::RuntimeError(error)) => error.error.to_string(),
+            result => panic!("expected a runtime error, got {result:?}"),
+        };
+
+        assert_eq!(
+            run_error("print 1.5 & 1"),
+            "number 1.5 has no integer representation"
+        );
+        assert_eq!(
+            run_error("print ~nil"),
+            "attempt to perform bitwise operation on a nil value"
+        );
+    }
+
+    #[test]
+    fn test_strings() {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load(
+                "string a = \"hello\"
+                function greeting() -> string return \"hello\" end
+                print a
+                print a == greeting()
+                print a == \"world\"
+                print a ~= 'hello'"
+                    .as_bytes(),
+            )
+            .expect("program should compile");
+
+        let mut output = Vec::new();
+        let mut vm = VirtualMachine::with_output(&chunk, &mut output);
+        vm.run_interpreter().expect("program should run");
+
+        // Both chunks have their own constant, but only one is kept
+        assert_eq!(vm.strings.len(), 2);
+        drop(vm);
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "hello\ntrue\nfalse\nfalse\n"
+        );
+    }
+
+    #[test]
+    fn test_concat_and_len() {
+        let output = interpret(
+            "string a = \"poke\" <> \"mon\"
+            print a
+            print a == \"pokemon\"
+            print \"x = \" <> 1 + 2 <> \", y = \" <> 0.5
+            print 1 <> 2
+            print #a
+            print #\"\" + #\"ñ\"
+            print #(a <> a)
+            table t = {1, 2, 3, x = 4}
+            print #t
+            t[5] = 5
+            t[2.0] = nil
+            print #t
+            print #{}",
+        )
+        .expect("program should run");
+        assert_eq!(
+            output,
+            "pokemon\ntrue\nx = 3, y = 0.5\n12\n7\n2\n14\n3\n1\n0\n"
+        );
+
+        let run_error = |source: &str| match interpret(source) {
+            Err(InterpretError::RuntimeError(error)) => error.error.to_string(),
+            result => panic!("expected a runtime error, got {result:?}"),
+        };
+
+        assert_eq!(
+            run_error("print \"a\" <> nil"),
+            "attempt to concatenate a nil value"
+        );
+        assert_eq!(
+            run_error("print true <> \"a\""),
+            "attempt to concatenate a bool value"
+        );
+        assert_eq!(
+            run_error("print #1"),
+            "attempt to get length of a int value"
+        );
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        let output = interpret(
+            "int a = 41
+            function f() end
+            print \"value is {a + 1}\"
+            print '{a} {a > 1} {nil} {f}{\"!\"}'
+            print \"nested {\"{a <> 'b'}\"} \\{a}\"
+            print \"value is {a + 1}\" == \"value is 42\"",
+        )
+        .expect("program should run");
+
+        assert_eq!(
+            output,
+            "value is 42\n41 true nil <fn f>!\nnested 41b {a}\ntrue\n"
+        );
+
+        let errors = Parser::new(&mut Chunk::new())
+            .load("print \"{1 2}\"".as_bytes())
+            .expect_err("interpolation must hold a single expression");
+        assert_eq!(
+            errors[0].error.to_string(),
+            "expected `}`, found number `2`"
+        );
+
+        let errors = Parser::new(&mut Chunk::new())
+            .load("print \"empty {} x\"".as_bytes())
+            .expect_err("interpolation must hold an expression");
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert_eq!(errors[0].error.to_string(), "expected expression");
+        assert_eq!((errors[0].span.column, errors[0].span.end), (14, 15));
+
+        let errors = Parser::new(&mut Chunk::new())
+            .load("print \"a {1\nprint 2 +".as_bytes())
+            .expect_err("interpolation must be closed");
+        let found: Vec<(String, i32)> = errors
+            .iter()
+            .map(|error| (error.error.to_string(), error.span.line))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("unterminated string interpolation".to_string(), 1),
+                ("expected expression".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_many_constants() {
+        let source: String = (0..300).map(|i| format!("print {i}\n")).collect();
+        let expected: String = (0..300).map(|i| format!("{i}\n")).collect();
+
+        assert_eq!(interpret(&source).expect("program should run"), expected);
+    }
+
+    #[test]
+    fn test_global_variables() {
+        let output = interpret(
+            "mut float a = 1
+            int b = 2
+            print a
+            a = a + b
+            print a
+            byte c = 255b
+            print c",
+        )
+        .expect("program should run");
+
+        assert_eq!(output, "1.0\n3.0\n255\n");
+
+        let error = match interpret("mut int a = 1\na = 2.5") {
+            Err(InterpretError::RuntimeError(error)) => error,
+            result => panic!("expected a runtime error, got {result:?}"),
+        };
+
+        assert!(matches!(
+            error.error,
+            RuntimeErrorType::MismatchedTypes {
+                expected: DataType::Int,
+                found: "float"
+            }
+        ));
+        assert_eq!(error.span.line, 2);
+        assert_eq!(error.span.column, 5);
+    }
+
+    #[test]
+    fn test_local_variables() {
+        let output = interpret(
+            "int a = 1
+            do
+                mut float a = a + 1
+                do
+                    float b = 0.5
+                    a = a * 3
+                    print a + b
+                end
+                print a
+            end
+            print a",
+        )
+        .expect("program should run");
+
+        assert_eq!(output, "6.5\n6.0\n1\n");
+    }
+
+    #[test]
+    fn test_if_statement() {
+        let output = interpret(
+            "int a = 2
+            if a then
+                int b = 3
+                print a * b
+            elseif a then
+                print 0
+            end
+            if a then print 1 else print 0 end",
+        )
+        .expect("program should run");
+
+        assert_eq!(output, "6\n1\n");
+
+        let output = interpret(
+            "if nil then print 1 else print 2 end
+            if false then print 1 elseif not nil then print 3 end
+            if 0 then print 4 end",
+        )
+        .expect("program should run");
+
+        // Like in Lua, zero is truthy
+        assert_eq!(output, "2\n3\n4\n");
+    }
+
+    #[test]
+    fn test_loops() {
+        let output = interpret(
+            "int a = 1
+            while a do
+                int b = 2
+                repeat
+                    int c = 3
+                    print b * c
+                until c
+                while b do
+                    int d = 4
+                    break
+                end
+                print b
+                break
+            end
+            print a",
+        )
+        .expect("program should run");
+
+        assert_eq!(output, "6\n2\n1\n");
+    }
+
+    #[test]
+    fn test_numeric_for() {
+        let output = interpret(
+            "for i = 1, 10, 3 do print i end
+            for i = 1, 2, 0.5 do print i end
+            for i = 3, 0.5, -1 do
+                int a = 2
+                print i
+            end
+            for i = 5, 1 do print i end
+            for i = 1, 3 do
+                for j = 1, 10 do
+                    int b = 0
+                    print j
+                    break
+                end
+            end",
+        )
+        .expect("program should run");
+
+        assert_eq!(output, "1\n4\n7\n10\n1.0\n1.5\n2.0\n3\n2\n1\n1\n1\n1\n");
+
+        let error = match interpret("for i = 1, 2, 0 do end") {
+            Err(InterpretError::RuntimeError(error)) => error,
+            result => panic!("expected a runtime error, got {result:?}"),
+        };
+
+        assert!(matches!(error.error, RuntimeErrorType::ZeroForStep));
+    }
+
+    #[test]
+    fn test_generic_for() {
+        let output = interpret(
+            "function upTo(int limit, int? last) -> int?
+                if last == nil then return 1 end
+                if last >= limit then return nil end
+                return last + 1
+            end
+            for value, rest in upTo, 3 do
+                print value
+                print rest
+            end
+            function once(int? state, float? last) -> float?
+                if last == nil then return 0.5 end
+                return
+            end
+            for value in once do print value end",
+        )
+        .expect("program should run");
+
+        assert_eq!(output, "1\nnil\n2\nnil\n3\nnil\n0.5\n");
+
+        // Only nullable types allow `nil`
+        let error = match interpret(
+            "function never(int? state, int? last) -> int return nil end
+            for value in never do print value end",
+        ) {
+            Err(InterpretError::RuntimeError(error)) => error,
+            result => panic!("expected a runtime error, got {result:?}"),
+        };
+
+        assert!(matches!(
+            error.error,
+            RuntimeErrorType::MismatchedTypes {
+                expected: DataType::Int,
+                found: "nil"
+            }
+        ));
+
+        // Tables are iterated in the order their keys were added
+        let output = interpret(
+            "table t = {10, 20, name = \"poke\", [true] = 1.5}
+            t[2] = nil
+            for k, v in t do
+                print k
+                print v
+                t[k] = nil
+            end
+            for k in t do print k end
+            for k, v, extra in {7} do print extra end",
+        )
+        .expect("program should run");
+
+        assert_eq!(output, "1\n10\nname\npoke\ntrue\n1.5\nnil\n");
+
+        let error = match interpret("for k, v in 1 do print k end") {
+            Err(InterpretError::RuntimeError(error)) => error,
+            result => panic!("expected a runtime error, got {result:?}"),
+        };
+
+        assert!(matches!(
+            error.error,
+            RuntimeErrorType::NotCallable { found: "int" }
+        ));
+    }
+
+    #[test]
+    fn test_tables() {
+        let output = interpret(
+            "table t = {1, 2; x = 3, [\"y\" <> \"z\"] = 4,}
+            print t[1] + t[2]
+            print t.x
+            print t.yz
+            print t.missing
+            t.x = \"x\"
+            t[1.0] = 5
+            t[2b] = t
+            print t.x
+            print t[1]
+            print t[2] == t
+            t[2].inner = {}
+            t.inner.deep = true
+            print t[\"inner\"][\"deep\"]
+            function get(table from, string key) -> int return from[key] end
+            print get({a = 8}, \"a\")
+            print {}",
+        )
+        .expect("program should run");
+
+        assert_eq!(output, "3\n3\n4\nnil\nx\n5\ntrue\ntrue\n8\n<table>\n");
+
+        let run_error = |source: &str| match interpret(source) {
+            Err(InterpretError::RuntimeError(error)) => error.error,
+            result => panic!("expected a runtime error, got {result:?}"),
+        };
+
+        assert!(matches!(
+            run_error("int a = 1\nprint a[1]"),
+            RuntimeErrorType::NotIndexable { found: "int" }
+        ));
+        assert!(matches!(
+            run_error("string s = \"s\"\ns.x = 1"),
+            RuntimeErrorType::NotIndexable { found: "string" }
+        ));
+        assert!(matches!(
+            run_error("table t = {}\nt[nil] = 1"),
+            RuntimeErrorType::InvalidKey { found: "nil" }
+        ));
+        assert!(matches!(
+            run_error("table t = {[0 / 0.0] = 1}"),
+            RuntimeErrorType::InvalidKey { found: "NaN" }
+        ));
+        assert!(matches!(
+            run_error("table t = 1"),
+            RuntimeErrorType::MismatchedTypes {
+                expected: DataType::Table,
+                found: "int"
+            }
+        ));
+    }
+
+    #[test]
+    fn test_functions() {
+        let output = interpret(
+            "function sum(int a, float b) -> float
+                return a + b
+            end
+            function greet()
+                print 1
+                return
+            end
+            print sum(1, 2)
+            greet()
+            print greet()
+            do
+                function local(byte a) -> byte return a end
+                print local(7b)
+            end",
+        )
+        .expect("program should run");
+
+        assert_eq!(output, "3.0\n1\n1\nnil\n7\n");
+    }
+
+    #[test]
+    fn test_forward_references() {
+        let output = interpret(
+            "function isEven(int n) -> bool
+                if n == 0 then return true end
+                return isOdd(n - 1)
+            end
+            function isOdd(int n) -> bool
+                if n == 0 then return false end
+                return isEven(n - 1)
+            end
+            function reset() total = 0 end
+            mut float total = 5
+            print isEven(10)
+            print isOdd(7)
+            print isEven(3)
+            reset()
+            print total",
+        )
+        .expect("program should run");
+
+        assert_eq!(output, "true\ntrue\nfalse\n0.0\n");
+
+        let run_error = |source: &str| match interpret(source) {
+            Err(InterpretError::RuntimeError(error)) => error.error,
+            result => panic!("expected a runtime error, got {result:?}"),
+        };
+
+        // Called before the global is declared
+        assert!(matches!(
+            run_error("function f() -> int return later end\nf()\nint later = 1"),
+            RuntimeErrorType::UndefinedVariable { name } if name == "later"
+        ));
+        assert!(matches!(
+            run_error("function f() later = true end\nmut int later = 1\nf()"),
+            RuntimeErrorType::MismatchedTypes {
+                expected: DataType::Int,
+                found: "bool"
+            }
+        ));
+    }
+
+    #[test]
+    fn test_function_errors() {
+        let run_error = |source: &str| match interpret(source) {
+            Err(InterpretError::RuntimeError(error)) => error.error,
+            result => panic!("expected a runtime error, got {result:?}"),
+        };
+
+        assert!(matches!(
+            run_error("function f(int a) end\nf(1, 2)"),
+            RuntimeErrorType::ArityMismatch {
+                expected: 1,
+                found: 2,
+                ..
+            }
+        ));
+        assert!(matches!(
+            run_error("function f(int a) end\nf(1.5)"),
+            RuntimeErrorType::MismatchedTypes {
+                expected: DataType::Int,
+                found: "float"
+            }
+        ));
+
+        // Reported at the returned value, or at the end the function falls off
+        let return_error = |source: &str| match interpret(source) {
+            Err(InterpretError::RuntimeError(error)) => error,
+            result => panic!("expected a runtime error, got {result:?}"),
+        };
+
+        let error = return_error("function f() -> int\n  return 1.5 + 1\nend\nf()");
+        assert!(matches!(
+            error.error,
+            RuntimeErrorType::MismatchedTypes {
+                expected: DataType::Int,
+                found: "float"
+            }
+        ));
+        assert_eq!((error.span.line, error.span.column), (2, 10));
+        assert_eq!(error.span.end - error.span.start, 7);
+
+        let error = return_error("function f() -> int\n  if false then return 1 end\nend\nf()");
+        assert!(matches!(
+            error.error,
+            RuntimeErrorType::MismatchedTypes {
+                expected: DataType::Int,
+                found: "nil"
+            }
+        ));
+        assert_eq!((error.span.line, error.span.column), (3, 1));
+        assert!(matches!(
+            run_error("int a = 1\na()"),
+            RuntimeErrorType::NotCallable { found: "int" }
+        ));
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load("function f(float depth) print depth f(depth + 1) end\nf(1)".as_bytes())
+            .expect("program should compile");
+
+        let mut output = Vec::new();
+        let error = VirtualMachine::with_output(&chunk, &mut output)
+            .with_max_depth(4)
+            .run_interpreter()
+            .expect_err("recursion should overflow");
+
+        let InterpretError::RuntimeError(error) = error;
+        assert!(matches!(error.error, RuntimeErrorType::StackOverflow));
+        assert_eq!(error.span.line, 1);
+
+        // The script's frame counts towards the depth
+        assert_eq!(String::from_utf8(output).unwrap(), "1.0\n2.0\n3.0\n");
+
+        // Below the script's own frame, no call is allowed
+        let mut output = Vec::new();
+        let error = VirtualMachine::with_output(&chunk, &mut output)
+            .with_max_depth(0)
+            .run_interpreter()
+            .expect_err("the first call should overflow");
+
+        let InterpretError::RuntimeError(error) = error;
+        assert!(matches!(error.error, RuntimeErrorType::StackOverflow));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_mut_params() {
+        let output = interpret(
+            "function increment(mut float n) n = n + 1 end
+            function twice(mut float n)
+                increment(n)
+                increment n
+            end
+            mut float a = 2
+            increment a
+            print a
+            twice(a)
+            print a
+            do
+                mut float b = 10
+                twice b
+                print b
+            end",
+        )
+        .expect("program should run");
+
+        assert_eq!(output, "3.0\n5.0\n12.0\n");
+
+        // The iterator of a `for` loop is given values, not variables
+        let error = interpret("function next(mut int a, int b) end\nfor x in next do end")
+            .expect_err("values can't be passed by reference");
+
+        let InterpretError::RuntimeError(error) = error;
+        assert!(
+            matches!(
+                error.error,
+                RuntimeErrorType::ExpectedReference { position: 1, .. }
+            ),
+            "{:?}",
+            error.error
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        let mut chunk = Chunk::new();
+        Parser::new(&mut chunk)
+            .load(
+                "function outer() -> float
+                    mut float count = 0
+                    function increment() count = count + 1 end
+                    function nested() -> float
+                        function deeper() increment() end
+                        deeper()
+                        return count
+                    end
+                    increment()
+                    print nested()
+                    return count
+                end
+                print outer()
+                do
+                    mut float total = 0
+                    for i = 1, 3 do
+                        function add() total = total + i end
+                        add()
+                    end
+                    print total
+                end
+                function twice(mut float x)
+                    function add() x = x + 1 end
+                    add()
+                    add()
+                end
+                mut float a = 1
+                twice a
+                print a"
+                    .as_bytes(),
+            )
+            .expect("program should compile");
+
+        let mut output = Vec::new();
+        let mut vm = VirtualMachine::with_output(&chunk, &mut output);
+        vm.run_interpreter().expect("program should run");
+
+        // Every captured local was closed when its scope ended
+        assert!(vm.open_upvalues.is_empty());
+        drop(vm);
+
+        assert_eq!(String::from_utf8(output).unwrap(), "2.0\n2.0\n6.0\n3.0\n");
     }
 }